@@ -30,38 +30,49 @@ impl From<Object> for CResult {
             }
             Object::Double(double) => {
                 let boxed = Box::new(double);
-                let result = CResult {
+                CResult {
                     int_result: ptr::null(),
                     float_result: Box::into_raw(boxed) as *const f64,
                     bool_result: ptr::null(),
                     string_result: ptr::null(),
                     error: ptr::null(),
-                };
-                result
+                }
             }
             Object::Bool(bool) => CResult {
                 int_result: ptr::null(),
                 float_result: ptr::null(),
                 bool_result: match bool {
-                    true => &(1 as u8) as *const u8,
-                    false => &(0 as u8) as *const u8,
+                    true => &1_u8 as *const u8,
+                    false => &0_u8 as *const u8,
                 },
                 string_result: ptr::null(),
                 error: ptr::null(),
             },
+            // Every pointer is left null, which is how a null result reaches C.
+            Object::Null => CResult {
+                int_result: ptr::null(),
+                float_result: ptr::null(),
+                bool_result: ptr::null(),
+                string_result: ptr::null(),
+                error: ptr::null(),
+            },
+            // TODO: expose lists and maps through `CResult`.
+            Object::List(_) => unsupported_result("lists"),
+            Object::Decimal(_) => unsupported_result("decimals"),
+            Object::BigInt(_) => unsupported_result("big integers"),
+            Object::Map(_) => unsupported_result("maps"),
             Object::Str(string) => {
                 // TODO: remove unwraps();
                 let string: String = Rc::into_inner(string).unwrap();
                 let c_string = CString::new(string).unwrap().into_raw() as *const u8;
                 // let c_string = CString::new().unwrap().into_raw();
-                let result = CResult {
+                CResult {
                     int_result: ptr::null(),
                     float_result: ptr::null(),
                     bool_result: ptr::null(),
                     string_result: c_string,
                     error: ptr::null(),
-                };
-                result
+                }
             }
         };
         // println!("result: {:?}", result);
@@ -69,6 +80,19 @@ impl From<Object> for CResult {
     }
 }
 
+/// An error result for the kind of value that `CResult` cannot yet hold.
+fn unsupported_result(kind: &str) -> CResult {
+    CResult {
+        int_result: ptr::null(),
+        float_result: ptr::null(),
+        bool_result: ptr::null(),
+        string_result: ptr::null(),
+        error: CString::new(format!("{kind} are not supported as a result"))
+            .unwrap()
+            .into_raw() as *const u8,
+    }
+}
+
 /* TODO:
 * 1. Need to expose a method for freeing the created CResult.
 * */
@@ -93,6 +117,8 @@ pub extern "C" fn free_cresult(result: CResult) {
     println!("free called");
 }
 
+// `formula` comes from C and is checked for null before being dereferenced.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[no_mangle]
 pub extern "C" fn evaluate(formula: *const c_char) -> CResult {
     if formula.is_null() {
@@ -106,7 +132,7 @@ pub extern "C" fn evaluate(formula: *const c_char) -> CResult {
             string_result: ptr::null(),
         };
     }
-    let formula: &CStr = unsafe { CStr::from_ptr(formula as *const i8) };
+    let formula: &CStr = unsafe { CStr::from_ptr(formula) };
     // ""
     let formula_str = match formula.to_str() {
         Ok(formula) => formula,
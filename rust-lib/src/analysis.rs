@@ -0,0 +1,78 @@
+use std::{collections::BTreeSet, rc::Rc};
+
+use crate::{
+    expression::Expression,
+    visitor::{walk_lambda, Visitor},
+};
+
+/// A function called by an expression, along with the number of arguments it
+/// was called with.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FunctionReference {
+    pub name: String,
+    pub arity: usize,
+}
+
+/// Everything an expression refers to by name.
+#[derive(Debug, Default, PartialEq)]
+pub struct References {
+    /// Names written in brackets, e.g. `[price]`.
+    pub parameters: BTreeSet<String>,
+    /// Names written without brackets, e.g. `price`.
+    pub identifiers: BTreeSet<String>,
+    pub functions: BTreeSet<FunctionReference>,
+}
+
+/// Collects the parameters, bare identifiers and functions used by `expression`.
+///
+/// The parameters of a lambda are not references: `map([items], x => x * [rate])`
+/// refers to `items` and `rate` only. Neither are the names bound by `let` within
+/// its body.
+pub fn references(expression: &Expression) -> References {
+    let mut collector = Collector {
+        references: References::default(),
+        bound: vec![],
+    };
+    collector.visit_expression(expression);
+    collector.references
+}
+
+struct Collector {
+    references: References,
+    // Parameters of the lambdas and names of the lets being visited.
+    bound: Vec<Rc<String>>,
+}
+
+impl Visitor for Collector {
+    fn visit_lambda(&mut self, parameters: &[Rc<String>], body: &Expression) {
+        self.bound.extend_from_slice(parameters);
+        walk_lambda(self, parameters, body);
+        self.bound.truncate(self.bound.len() - parameters.len());
+    }
+
+    fn visit_let(&mut self, name: &Rc<String>, value: &Expression, body: &Expression) {
+        self.visit_expression(value);
+        self.bound.push(name.clone());
+        self.visit_expression(body);
+        self.bound.pop();
+    }
+
+    fn visit_ident(&mut self, ident: &Rc<String>) {
+        if !self.bound.contains(ident) {
+            self.references.parameters.insert(ident.to_string());
+        }
+    }
+
+    fn visit_bare_ident(&mut self, ident: &Rc<String>) {
+        if !self.bound.contains(ident) {
+            self.references.identifiers.insert(ident.to_string());
+        }
+    }
+
+    fn visit_function_name(&mut self, name: &Rc<String>, arity: usize) {
+        self.references.functions.insert(FunctionReference {
+            name: name.to_string(),
+            arity,
+        });
+    }
+}
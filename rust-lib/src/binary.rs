@@ -0,0 +1,564 @@
+//! Compact binary encoding of parsed expressions.
+//!
+//! The layout is the magic bytes `NCLC`, a format version byte, a table of the
+//! strings used by the expression (identifiers, member and function names and
+//! string literals, each stored once) and the expression itself, written depth first
+//! as one tag byte per node followed by its payload. Integers are LEB128
+//! varints, signed ones zigzag encoded first, doubles are 8 little endian
+//! bytes, decimals the 16 bytes of `Decimal::serialize` and big integers their
+//! length followed by their little endian two's complement bytes.
+
+use std::{collections::HashMap, rc::Rc};
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use rust_decimal::Decimal;
+
+use crate::{
+    expression::{Expression, TemplatePart},
+    token::Token,
+};
+
+pub const FORMAT_VERSION: u8 = 1;
+
+const MAGIC: &[u8; 4] = b"NCLC";
+// Deeper trees are rejected rather than overflowing the stack when decoding,
+// which for a debug build on a 2 MiB thread already happens below 512. Encoding
+// rejects them too, so that whatever is encoded can be decoded. The left
+// operands of a chain like `a + b + c` are read in a loop, so such a chain
+// counts as one level however long it is.
+const MAX_DEPTH: usize = 256;
+
+const TAG_NIL: u8 = 0;
+const TAG_INT: u8 = 1;
+const TAG_DOUBLE: u8 = 2;
+const TAG_FALSE: u8 = 3;
+const TAG_TRUE: u8 = 4;
+const TAG_IDENT: u8 = 5;
+const TAG_BARE_IDENT: u8 = 6;
+const TAG_STR: u8 = 7;
+const TAG_PREFIX: u8 = 8;
+const TAG_INFIX: u8 = 9;
+const TAG_CALL: u8 = 10;
+const TAG_NULL: u8 = 11;
+const TAG_LIST: u8 = 12;
+const TAG_INDEX: u8 = 13;
+const TAG_LAMBDA: u8 = 14;
+const TAG_MEMBER: u8 = 15;
+const TAG_LET: u8 = 16;
+const TAG_TEMPLATE: u8 = 17;
+const TAG_DECIMAL: u8 = 18;
+const TAG_BIG_INT: u8 = 19;
+const TAG_NULL_SAFE_MEMBER: u8 = 20;
+
+// Kinds of the parts of a template.
+const PART_TEXT: u8 = 0;
+const PART_VALUE: u8 = 1;
+const PART_FORMATTED_VALUE: u8 = 2;
+
+// The index of an operator in this table is its code.
+const OPERATORS: &[Token] = &[
+    Token::Bang,
+    Token::Not,
+    Token::Asterisk,
+    Token::Slash,
+    Token::Percent,
+    Token::Plus,
+    Token::Minus,
+    Token::LessThan,
+    Token::GreaterThan,
+    Token::LessThanEqualTo,
+    Token::GreaterThanEqualTo,
+    Token::Equals,
+    Token::NotEquals,
+    Token::NotEqualsAngleBrackets,
+    Token::And,
+    Token::DoubleAmpersand,
+    Token::Or,
+    Token::DoublePipe,
+    Token::DoubleQuestionMark,
+    Token::In,
+    Token::NotIn,
+    Token::DoubleAsterisk,
+    Token::DoubleSlash,
+];
+
+#[derive(Debug, PartialEq)]
+pub enum BinaryError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnexpectedEnd,
+    InvalidTag(u8),
+    InvalidVarint,
+    InvalidOperator(u8),
+    InvalidStringIndex(u64),
+    InvalidUtf8,
+    TooDeep,
+    UnExpectedNil,
+    TrailingBytes,
+    InvalidDecimal,
+    InvalidBigInt,
+    UnsupportedToken(Token),
+}
+
+pub fn encode(expression: &Expression) -> Result<Vec<u8>, BinaryError> {
+    let mut encoder = Encoder {
+        strings: vec![],
+        string_indexes: HashMap::new(),
+        body: vec![],
+    };
+    encoder.write_expression(expression, 0)?;
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(FORMAT_VERSION);
+    write_varint(&mut bytes, encoder.strings.len() as u64);
+    for string in &encoder.strings {
+        write_varint(&mut bytes, string.len() as u64);
+        bytes.extend_from_slice(string.as_bytes());
+    }
+    bytes.extend_from_slice(&encoder.body);
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Expression, BinaryError> {
+    let mut decoder = Decoder {
+        bytes,
+        position: 0,
+        strings: vec![],
+    };
+    if decoder.read_bytes(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+        return Err(BinaryError::BadMagic);
+    }
+    match decoder.read_byte()? {
+        FORMAT_VERSION => (),
+        version => return Err(BinaryError::UnsupportedVersion(version)),
+    }
+
+    let string_count = decoder.read_varint()?;
+    for _ in 0..string_count {
+        let len = decoder.read_varint()?;
+        let len = usize::try_from(len).map_err(|_| BinaryError::UnexpectedEnd)?;
+        let string =
+            std::str::from_utf8(decoder.read_bytes(len)?).map_err(|_| BinaryError::InvalidUtf8)?;
+        decoder.strings.push(Rc::new(string.to_string()));
+    }
+
+    let expression = decoder.read_expression(0)?;
+    match decoder.position == bytes.len() {
+        true => Ok(expression),
+        false => Err(BinaryError::TrailingBytes),
+    }
+}
+
+struct Encoder {
+    strings: Vec<Rc<String>>,
+    string_indexes: HashMap<Rc<String>, u64>,
+    body: Vec<u8>,
+}
+
+impl Encoder {
+    fn write_string(&mut self, string: &Rc<String>) {
+        let index = match self.string_indexes.get(string) {
+            Some(index) => *index,
+            None => {
+                let index = self.strings.len() as u64;
+                self.strings.push(string.clone());
+                self.string_indexes.insert(string.clone(), index);
+                index
+            }
+        };
+        write_varint(&mut self.body, index);
+    }
+
+    fn write_operator(&mut self, operator: &Token) -> Result<(), BinaryError> {
+        match OPERATORS.iter().position(|known| known == operator) {
+            Some(code) => {
+                self.body.push(code as u8);
+                Ok(())
+            }
+            None => Err(BinaryError::UnsupportedToken(operator.clone())),
+        }
+    }
+
+    fn write_expression(
+        &mut self,
+        expression: &Expression,
+        depth: usize,
+    ) -> Result<(), BinaryError> {
+        if depth > MAX_DEPTH {
+            return Err(BinaryError::TooDeep);
+        }
+        match expression {
+            Expression::Nil => return Err(BinaryError::UnExpectedNil),
+            Expression::Int(int) => {
+                self.body.push(TAG_INT);
+                write_varint(&mut self.body, zigzag(*int));
+            }
+            Expression::Double(double) => {
+                self.body.push(TAG_DOUBLE);
+                self.body.extend_from_slice(&double.to_le_bytes());
+            }
+            Expression::Decimal(decimal) => {
+                self.body.push(TAG_DECIMAL);
+                self.body.extend_from_slice(&decimal.serialize());
+            }
+            Expression::BigInt(int) => {
+                let bytes = int.to_signed_bytes_le();
+                self.body.push(TAG_BIG_INT);
+                write_varint(&mut self.body, bytes.len() as u64);
+                self.body.extend_from_slice(&bytes);
+            }
+            Expression::Bool(false) => self.body.push(TAG_FALSE),
+            Expression::Bool(true) => self.body.push(TAG_TRUE),
+            Expression::Null => self.body.push(TAG_NULL),
+            Expression::Ident(ident) => {
+                self.body.push(TAG_IDENT);
+                self.write_string(ident);
+            }
+            Expression::BareIdent(ident) => {
+                self.body.push(TAG_BARE_IDENT);
+                self.write_string(ident);
+            }
+            Expression::Str(string) => {
+                self.body.push(TAG_STR);
+                self.write_string(string);
+            }
+            Expression::PrefixExpression {
+                operator,
+                expression,
+            } => {
+                self.body.push(TAG_PREFIX);
+                self.write_operator(operator)?;
+                self.write_expression(expression, depth + 1)?;
+            }
+            Expression::InfixExpression { .. } => {
+                // The chain of left operands is written without recursing,
+                // outermost operator first, then the innermost right operand
+                // last.
+                let mut rights = vec![];
+                let mut left = expression;
+                while let Expression::InfixExpression {
+                    operator,
+                    left: next,
+                    right,
+                } = left
+                {
+                    self.body.push(TAG_INFIX);
+                    self.write_operator(operator)?;
+                    rights.push(right);
+                    left = next;
+                }
+                self.write_expression(left, depth + 1)?;
+                for right in rights.into_iter().rev() {
+                    self.write_expression(right, depth + 1)?;
+                }
+            }
+            Expression::CallExpression {
+                function,
+                arguments,
+            } => {
+                self.body.push(TAG_CALL);
+                self.write_expression(function, depth + 1)?;
+                write_varint(&mut self.body, arguments.len() as u64);
+                for argument in arguments {
+                    self.write_expression(argument, depth + 1)?;
+                }
+            }
+            Expression::List(elements) => {
+                self.body.push(TAG_LIST);
+                write_varint(&mut self.body, elements.len() as u64);
+                for element in elements {
+                    self.write_expression(element, depth + 1)?;
+                }
+            }
+            Expression::Lambda { parameters, body } => {
+                self.body.push(TAG_LAMBDA);
+                write_varint(&mut self.body, parameters.len() as u64);
+                for parameter in parameters {
+                    self.write_string(parameter);
+                }
+                self.write_expression(body, depth + 1)?;
+            }
+            Expression::IndexExpression { expression, index } => {
+                self.body.push(TAG_INDEX);
+                self.write_expression(expression, depth + 1)?;
+                self.write_expression(index, depth + 1)?;
+            }
+            Expression::MemberExpression {
+                expression,
+                member,
+                null_safe,
+            } => {
+                self.body.push(match null_safe {
+                    true => TAG_NULL_SAFE_MEMBER,
+                    false => TAG_MEMBER,
+                });
+                self.write_expression(expression, depth + 1)?;
+                self.write_string(member);
+            }
+            Expression::Template(parts) => {
+                self.body.push(TAG_TEMPLATE);
+                write_varint(&mut self.body, parts.len() as u64);
+                for part in parts {
+                    match part {
+                        TemplatePart::Text(text) => {
+                            self.body.push(PART_TEXT);
+                            self.write_string(text);
+                        }
+                        TemplatePart::Value {
+                            expression,
+                            format: None,
+                        } => {
+                            self.body.push(PART_VALUE);
+                            self.write_expression(expression, depth + 1)?;
+                        }
+                        TemplatePart::Value {
+                            expression,
+                            format: Some(format),
+                        } => {
+                            self.body.push(PART_FORMATTED_VALUE);
+                            self.write_expression(expression, depth + 1)?;
+                            self.write_string(format);
+                        }
+                    }
+                }
+            }
+            Expression::Let { name, value, body } => {
+                self.body.push(TAG_LET);
+                self.write_string(name);
+                self.write_expression(value, depth + 1)?;
+                self.write_expression(body, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    strings: Vec<Rc<String>>,
+}
+
+impl<'a> Decoder<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], BinaryError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(BinaryError::UnexpectedEnd)?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, BinaryError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_varint(&mut self) -> Result<u64, BinaryError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(BinaryError::InvalidVarint)
+    }
+
+    fn read_string(&mut self) -> Result<Rc<String>, BinaryError> {
+        let index = self.read_varint()?;
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| self.strings.get(i))
+            .cloned()
+            .ok_or(BinaryError::InvalidStringIndex(index))
+    }
+
+    fn read_operator(&mut self) -> Result<Token, BinaryError> {
+        let code = self.read_byte()?;
+        OPERATORS
+            .get(usize::from(code))
+            .cloned()
+            .ok_or(BinaryError::InvalidOperator(code))
+    }
+
+    fn read_infix_operator(&mut self) -> Result<Token, BinaryError> {
+        let code_position = self.position;
+        let operator = self.read_operator()?;
+        match operator.is_infix_operator() {
+            true => Ok(operator),
+            false => Err(BinaryError::InvalidOperator(self.bytes[code_position])),
+        }
+    }
+
+    /// Reads a count followed by that many expressions.
+    #[allow(clippy::vec_box)]
+    fn read_expressions(&mut self, depth: usize) -> Result<Vec<Box<Expression>>, BinaryError> {
+        let count = self.read_varint()?;
+        // Every expression takes at least one byte, which bounds the
+        // allocation for corrupt counts.
+        let remaining = (self.bytes.len() - self.position) as u64;
+        if count > remaining {
+            return Err(BinaryError::UnexpectedEnd);
+        }
+        let mut expressions = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            expressions.push(Box::new(self.read_expression(depth)?));
+        }
+        Ok(expressions)
+    }
+
+    fn read_decimal(&mut self) -> Result<Decimal, BinaryError> {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(self.read_bytes(16)?);
+        let decimal = Decimal::deserialize(bytes);
+        // Bytes that do not read back the same have unused bits or a scale
+        // beyond 28 set.
+        match decimal.serialize() == bytes {
+            true => Ok(decimal),
+            false => Err(BinaryError::InvalidDecimal),
+        }
+    }
+
+    fn read_big_int(&mut self) -> Result<BigInt, BinaryError> {
+        let len = self.read_varint()?;
+        let len = usize::try_from(len).map_err(|_| BinaryError::UnexpectedEnd)?;
+        let bytes = self.read_bytes(len)?;
+        let int = BigInt::from_signed_bytes_le(bytes);
+        // As the parser gives, only integers beyond i64 are big integers, and
+        // they are written without redundant sign bytes.
+        match int.to_i64().is_none() && int.to_signed_bytes_le() == bytes {
+            true => Ok(int),
+            false => Err(BinaryError::InvalidBigInt),
+        }
+    }
+
+    fn read_template_parts(&mut self, depth: usize) -> Result<Vec<TemplatePart>, BinaryError> {
+        let count = self.read_varint()?;
+        let mut parts = vec![];
+        for _ in 0..count {
+            parts.push(match self.read_byte()? {
+                PART_TEXT => TemplatePart::Text(self.read_string()?),
+                PART_VALUE => TemplatePart::Value {
+                    expression: Box::new(self.read_expression(depth)?),
+                    format: None,
+                },
+                PART_FORMATTED_VALUE => TemplatePart::Value {
+                    expression: Box::new(self.read_expression(depth)?),
+                    format: Some(self.read_string()?),
+                },
+                kind => return Err(BinaryError::InvalidTag(kind)),
+            });
+        }
+        Ok(parts)
+    }
+
+    fn read_expression(&mut self, depth: usize) -> Result<Expression, BinaryError> {
+        if depth > MAX_DEPTH {
+            return Err(BinaryError::TooDeep);
+        }
+        let expression = match self.read_byte()? {
+            TAG_NIL => return Err(BinaryError::UnExpectedNil),
+            TAG_INT => Expression::Int(unzigzag(self.read_varint()?)),
+            TAG_DOUBLE => {
+                let mut double = [0u8; 8];
+                double.copy_from_slice(self.read_bytes(8)?);
+                Expression::Double(f64::from_le_bytes(double))
+            }
+            TAG_DECIMAL => Expression::Decimal(self.read_decimal()?),
+            TAG_BIG_INT => Expression::BigInt(Rc::new(self.read_big_int()?)),
+            TAG_FALSE => Expression::Bool(false),
+            TAG_TRUE => Expression::Bool(true),
+            TAG_NULL => Expression::Null,
+            TAG_IDENT => Expression::Ident(self.read_string()?),
+            TAG_BARE_IDENT => Expression::BareIdent(self.read_string()?),
+            TAG_STR => Expression::Str(self.read_string()?),
+            TAG_PREFIX => {
+                let code_position = self.position;
+                let operator = self.read_operator()?;
+                if !operator.is_prefix_operator() {
+                    return Err(BinaryError::InvalidOperator(self.bytes[code_position]));
+                }
+                Expression::PrefixExpression {
+                    operator,
+                    expression: Box::new(self.read_expression(depth + 1)?),
+                }
+            }
+            TAG_INFIX => {
+                let mut operators = vec![self.read_infix_operator()?];
+                while self.bytes.get(self.position) == Some(&TAG_INFIX) {
+                    self.position += 1;
+                    operators.push(self.read_infix_operator()?);
+                }
+                let mut expression = self.read_expression(depth + 1)?;
+                for operator in operators.into_iter().rev() {
+                    expression = Expression::InfixExpression {
+                        operator,
+                        left: Box::new(expression),
+                        right: Box::new(self.read_expression(depth + 1)?),
+                    };
+                }
+                expression
+            }
+            TAG_CALL => {
+                let function = self.read_expression(depth + 1)?;
+                Expression::CallExpression {
+                    function: Box::new(function),
+                    arguments: self.read_expressions(depth + 1)?,
+                }
+            }
+            TAG_LIST => Expression::List(self.read_expressions(depth + 1)?),
+            TAG_LAMBDA => {
+                let count = self.read_varint()?;
+                let mut parameters = vec![];
+                for _ in 0..count {
+                    parameters.push(self.read_string()?);
+                }
+                Expression::Lambda {
+                    parameters,
+                    body: Box::new(self.read_expression(depth + 1)?),
+                }
+            }
+            TAG_INDEX => Expression::IndexExpression {
+                expression: Box::new(self.read_expression(depth + 1)?),
+                index: Box::new(self.read_expression(depth + 1)?),
+            },
+            tag @ (TAG_MEMBER | TAG_NULL_SAFE_MEMBER) => Expression::MemberExpression {
+                expression: Box::new(self.read_expression(depth + 1)?),
+                member: self.read_string()?,
+                null_safe: tag == TAG_NULL_SAFE_MEMBER,
+            },
+            TAG_LET => Expression::Let {
+                name: self.read_string()?,
+                value: Box::new(self.read_expression(depth + 1)?),
+                body: Box::new(self.read_expression(depth + 1)?),
+            },
+            TAG_TEMPLATE => Expression::Template(self.read_template_parts(depth + 1)?),
+            tag => return Err(BinaryError::InvalidTag(tag)),
+        };
+        Ok(expression)
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
@@ -1,11 +1,14 @@
 #![allow(dead_code, unused_variables)]
-use std::rc::Rc;
+use std::{cmp::Ordering, collections::HashMap, rc::Rc};
+
+use num_bigint::BigInt;
+use num_traits::{Pow, Signed, ToPrimitive, Zero};
+use rust_decimal::{Decimal, MathematicalOps, RoundingStrategy};
 
 use crate::{
-    expression::{Expression, ParseExpressionError},
-    lexer::Lexer,
-    object::{Context, Object},
-    parser::Parser,
+    expression::{Expression, ParseExpressionError, Script, TemplatePart},
+    object::{Context, DivisionByZeroPolicy, EvalOptions, MissingParameterPolicy, Object},
+    parser,
     token::Token,
 };
 
@@ -34,6 +37,29 @@ pub enum EvalError {
         found: u8,
     },
     ReferenceNotFound(String),
+    // The member expression, e.g. `[customer].address`, naming the missing key.
+    MemberNotFound(String),
+    UnknownFunction(String),
+    IndexOutOfBounds {
+        index: i64,
+        len: usize,
+    },
+    // Integer `//` or `%` by zero, or any division by zero under
+    // `DivisionByZeroPolicy::Error`.
+    DivideByZero,
+    // A NaN or infinite result under `EvalOptions::reject_non_finite`.
+    NonFiniteResult(f64),
+    // A decimal result beyond the range of `Decimal`, or a power of big
+    // integers too large to compute.
+    ArithmeticOverflow {
+        operator: Token,
+        left: Object,
+        right: Object,
+    },
+    InvalidFormatSpecifier {
+        format: String,
+        found: Object,
+    },
     UnExpectedOperatorOperandFound {
         operator: Token,
         left: Object,
@@ -42,22 +68,40 @@ pub enum EvalError {
 }
 
 pub fn eval_input(input: &str, context: Context) -> Result<Object, EvalError> {
-    let mut parser = Parser::new(Lexer::from_input(input))
+    let expression = parser::parse_input_with(input, &context.options())
         .map_err(|err| EvalError::ParseExpressionError { error: err })?;
 
-    let expression = parser
-        .parse()
+    eval(&expression, context)
+}
+
+/// Parses `input` as a script, see [`crate::parser::parse_script`], and runs it
+/// with [`eval_script`].
+pub fn eval_script_input(input: &str, context: Context) -> Result<Context, EvalError> {
+    let script = parser::parse_script_with(input, &context.options())
         .map_err(|err| EvalError::ParseExpressionError { error: err })?;
+    eval_script(&script, context)
+}
 
-    eval(&expression, context)
+/// Runs the statements of `script` in order, each one setting its target in
+/// `context`, and returns the resulting context. Later statements see the
+/// values set by earlier ones.
+pub fn eval_script(script: &Script, mut context: Context) -> Result<Context, EvalError> {
+    for statement in &script.statements {
+        let value = eval(&statement.value, context.clone())?;
+        context.set(&statement.target, value);
+    }
+    Ok(context)
 }
 
-fn eval(expression: &Expression, context: Context) -> Result<Object, EvalError> {
+pub(crate) fn eval(expression: &Expression, context: Context) -> Result<Object, EvalError> {
     match &expression {
         Expression::Int(val) => Ok(Object::Int(*val)),
         Expression::Double(val) => Ok(Object::Double(*val)),
+        Expression::Decimal(val) => Ok(Object::Decimal(*val)),
+        Expression::BigInt(val) => Ok(Object::BigInt(val.clone())),
         Expression::Str(val) => Ok(Object::Str(val.clone())),
         Expression::Bool(val) => Ok(Object::Bool(*val)),
+        Expression::Null => Ok(Object::Null),
         Expression::InfixExpression {
             operator,
             left,
@@ -71,7 +115,36 @@ fn eval(expression: &Expression, context: Context) -> Result<Object, EvalError>
             function,
             arguments,
         } => eval_fn_call(function, arguments.as_slice(), context),
-        Expression::Ident(ident) => eval_ident(ident, context.clone()),
+        Expression::Ident(ident) | Expression::BareIdent(ident) => {
+            eval_ident(ident, context.clone())
+        }
+        Expression::List(elements) => elements
+            .iter()
+            .map(|element| eval(element, context.clone()))
+            .collect::<Result<Vec<Object>, EvalError>>()
+            .map(|list| Object::List(Rc::new(list))),
+        Expression::IndexExpression { expression, index } => {
+            eval_index_expression(expression, index, context)
+        }
+        Expression::MemberExpression {
+            expression: map,
+            member,
+            null_safe,
+        } => eval_member_expression(expression, map, member, *null_safe, context),
+        Expression::Template(parts) => eval_template(parts, context),
+        // The value is evaluated once, however often the body uses it.
+        Expression::Let { name, value, body } => {
+            let value = eval(value, context.clone())?;
+            eval(
+                body,
+                context.child(HashMap::from_iter([(name.to_string(), value)])),
+            )
+        }
+        // Lambdas are only evaluated through the functions taking them.
+        Expression::Lambda { .. } => Err(EvalError::ExpectedExpressionXFoundY {
+            expected: "value",
+            found: expression.clone(),
+        }),
         _ => unimplemented!(),
     }
 }
@@ -81,6 +154,19 @@ fn eval_fn_call(
     arguments: &[Box<Expression>],
     context: Context,
 ) -> Result<Object, EvalError> {
+    // A method call is sugar for the function of the same name, taking the
+    // receiver as its first argument: `[name].ToUpper()` is `ToUpper([name])`.
+    if let Expression::MemberExpression {
+        expression: receiver,
+        member,
+        ..
+    } = function_expression
+    {
+        let arguments: Vec<Box<Expression>> = std::iter::once(receiver.clone())
+            .chain(arguments.iter().cloned())
+            .collect();
+        return eval_fn_call(&Expression::Ident(member.clone()), &arguments, context);
+    }
     let Expression::Ident(function_name) = function_expression else {
         return Err(EvalError::ExpectedExpressionXFoundY {
             expected: "function name",
@@ -88,25 +174,425 @@ fn eval_fn_call(
         });
     };
 
-    let _ = match function_name.as_str() {
-        "if" => (),
-        _ => unimplemented!(),
+    // These functions decide themselves which of their arguments get evaluated.
+    match function_name.as_str() {
+        "if" => return eval_if_fn_call(arguments, context),
+        "ifnull" => return eval_ifnull_fn_call(arguments, context),
+        "coalesce" => return eval_coalesce_fn_call(arguments, context),
+        "map" | "filter" => return eval_map_or_filter_fn_call(function_name, arguments, context),
+        "reduce" => return eval_reduce_fn_call(arguments, context),
+        _ => (),
     };
 
-    if arguments.len() != 3 {
-        return Err(EvalError::MissMatchFunctionArguements {
-            expected: 3,
-            found: arguments.len() as u8,
+    let arguments = arguments
+        .iter()
+        .map(|argument| eval(argument, context.clone()))
+        .collect::<Result<Vec<Object>, EvalError>>()?;
+    check_finite(
+        call_builtin_function(function_name, arguments, context.options())?,
+        context.options(),
+    )
+}
+
+/// Fails on a NaN or infinite result when the options ask for it.
+fn check_finite(value: Object, options: EvalOptions) -> Result<Object, EvalError> {
+    match value {
+        Object::Double(double) if options.reject_non_finite && !double.is_finite() => {
+            Err(EvalError::NonFiniteResult(double))
+        }
+        value => Ok(value),
+    }
+}
+
+fn call_builtin_function(
+    function_name: &str,
+    arguments: Vec<Object>,
+    options: EvalOptions,
+) -> Result<Object, EvalError> {
+    match function_name {
+        "isnull" => {
+            expect_arguments(1, arguments.len())?;
+            Ok(Object::Bool(arguments[0] == Object::Null))
+        }
+        "Sum" | "Avg" | "Count" | "Min" | "Max" | "Distinct" | "Sort" | "Any" | "All" => {
+            match aggregated_values(arguments) {
+                Some(values) => call_aggregate_function(function_name, &values, options),
+                None => Ok(Object::Null),
+            }
+        }
+        "ToUpper" | "ToLower" | "Trim" | "Length" => match <[Object; 1]>::try_from(arguments) {
+            Ok([value]) => call_string_function(function_name, value),
+            Err(arguments) => Err(EvalError::MissMatchFunctionArguements {
+                expected: 1,
+                found: arguments.len() as u8,
+            }),
+        },
+        "Round" | "Floor" | "Ceiling" | "Truncate" => match (function_name, &arguments[..]) {
+            (_, [value]) => call_rounding_function(function_name, value.clone(), 0),
+            ("Round", [value, digits]) => match digits {
+                Object::Null => Ok(Object::Null),
+                Object::Int(digits) if *digits >= 0 => {
+                    call_rounding_function(function_name, value.clone(), *digits)
+                }
+                found => Err(EvalError::ExpectedObjectXFoundY {
+                    expected: "non-negative int",
+                    found: found.clone(),
+                }),
+            },
+            _ => Err(EvalError::MissMatchFunctionArguements {
+                expected: if function_name == "Round" { 2 } else { 1 },
+                found: arguments.len() as u8,
+            }),
+        },
+        _ => Err(EvalError::UnknownFunction(function_name.to_string())),
+    }
+}
+
+/// `Round`, `Floor`, `Ceiling` and `Truncate` keep the type of their number,
+/// giving null for null. Decimals are rounded exactly, and `Round` rounds
+/// midpoints to the even neighbour like NCalc does, so `Round(2.5)` is 2.
+/// Integers are already whole and come back unchanged.
+fn call_rounding_function(
+    function_name: &str,
+    value: Object,
+    digits: i64,
+) -> Result<Object, EvalError> {
+    match value {
+        Object::Null | Object::Int(_) | Object::BigInt(_) => Ok(value),
+        Object::Double(double) => Ok(Object::Double(match function_name {
+            "Floor" => double.floor(),
+            "Ceiling" => double.ceil(),
+            "Truncate" => double.trunc(),
+            // A double has no more than 15 significant decimal digits to round.
+            _ if digits > 15 => double,
+            _ => {
+                let scale = 10f64.powi(digits as i32);
+                match (double * scale).is_finite() {
+                    true => (double * scale).round_ties_even() / scale,
+                    false => double,
+                }
+            }
+        })),
+        Object::Decimal(decimal) => Ok(Object::Decimal(match function_name {
+            "Floor" => decimal.floor(),
+            "Ceiling" => decimal.ceil(),
+            "Truncate" => decimal.trunc(),
+            _ => decimal.round_dp_with_strategy(
+                digits.min(u32::MAX as i64) as u32,
+                RoundingStrategy::MidpointNearestEven,
+            ),
+        })),
+        found => Err(EvalError::ExpectedObjectXFoundY {
+            expected: "number",
+            found,
+        }),
+    }
+}
+
+/// Functions of one string, giving null for null. `Length` also counts the
+/// elements of a list.
+fn call_string_function(function_name: &str, value: Object) -> Result<Object, EvalError> {
+    let string = match (function_name, value) {
+        (_, Object::Null) => return Ok(Object::Null),
+        ("Length", Object::List(list)) => return Ok(Object::Int(list.len() as i64)),
+        (_, Object::Str(string)) => string,
+        (_, found) => {
+            return Err(EvalError::ExpectedObjectXFoundY {
+                expected: "string",
+                found,
+            })
+        }
+    };
+    Ok(match function_name {
+        "ToUpper" => Object::Str(Rc::new(string.to_uppercase())),
+        "ToLower" => Object::Str(Rc::new(string.to_lowercase())),
+        "Trim" => Object::Str(Rc::new(string.trim().to_string())),
+        _ => Object::Int(string.chars().count() as i64),
+    })
+}
+
+/// Aggregates take either one list or the values to aggregate as separate
+/// arguments, e.g. `Max([prices])` or `Max([a], [b])`. `None` stands for a
+/// null list.
+fn aggregated_values(arguments: Vec<Object>) -> Option<Rc<Vec<Object>>> {
+    match <[Object; 1]>::try_from(arguments) {
+        Ok([Object::List(list)]) => Some(list),
+        Ok([Object::Null]) => None,
+        Ok([value]) => Some(Rc::new(vec![value])),
+        Err(arguments) => Some(Rc::new(arguments)),
+    }
+}
+
+/// The numeric aggregates skip null elements, like their SQL counterparts, and
+/// give null when that leaves nothing to aggregate, except for `Sum` which
+/// gives 0. `Sum` and `Avg` add the elements like `+` does: a sum of ints is a
+/// double, unless the options promote to big integers where it stays exact, and
+/// a sum with a decimal is a decimal. `Min` and `Max` return the element
+/// itself.
+///
+/// `Any` and `All` follow the three-valued logic of `or` and `and`.
+fn call_aggregate_function(
+    function_name: &str,
+    values: &[Object],
+    options: EvalOptions,
+) -> Result<Object, EvalError> {
+    match function_name {
+        "Count" => Ok(Object::Int(values.len() as i64)),
+        "Sum" => {
+            let zero = match options.promote_to_big_int {
+                true => Object::Int(0),
+                false => Object::Double(0.0),
+            };
+            numeric_values(values)?
+                .into_iter()
+                .try_fold(zero, |sum, value| {
+                    apply_numeric_operator(Token::Plus, sum, value.clone(), options)
+                })
+        }
+        "Avg" => match numeric_values(values)?.len() {
+            0 => Ok(Object::Null),
+            count => apply_numeric_operator(
+                Token::Slash,
+                call_aggregate_function("Sum", values, options)?,
+                Object::Int(count as i64),
+                options,
+            ),
+        },
+        "Min" | "Max" => {
+            let keep_right = match function_name {
+                "Min" => Ordering::Less,
+                _ => Ordering::Greater,
+            };
+            let extreme = numeric_values(values)?.into_iter().reduce(|left, right| {
+                match compare_numbers(right, left) == keep_right {
+                    true => right,
+                    false => left,
+                }
+            });
+            Ok(extreme.map_or(Object::Null, Object::clone))
+        }
+        "Distinct" => {
+            let mut distinct: Vec<Object> = vec![];
+            for value in values {
+                let mut is_duplicate = false;
+                for kept in &distinct {
+                    is_duplicate = match (kept, value) {
+                        (Object::Null, Object::Null) => true,
+                        (Object::Null, _) | (_, Object::Null) => false,
+                        _ => {
+                            compare_objects(Token::Equals, kept.clone(), value.clone(), options)?
+                                == Object::Bool(true)
+                        }
+                    };
+                    if is_duplicate {
+                        break;
+                    }
+                }
+                if !is_duplicate {
+                    distinct.push(value.clone());
+                }
+            }
+            Ok(Object::List(Rc::new(distinct)))
+        }
+        "Sort" => sort_values(values),
+        "Any" | "All" => {
+            let short_circuit_on = function_name == "Any";
+            let mut result = Object::Bool(!short_circuit_on);
+            for value in values {
+                match value {
+                    Object::Bool(value) if *value == short_circuit_on => {
+                        return Ok(Object::Bool(short_circuit_on))
+                    }
+                    Object::Bool(_) => (),
+                    Object::Null => result = Object::Null,
+                    _ => {
+                        return Err(EvalError::ExpectedObjectXFoundY {
+                            expected: "bool",
+                            found: value.clone(),
+                        })
+                    }
+                }
+            }
+            Ok(result)
+        }
+        _ => Err(EvalError::UnknownFunction(function_name.to_string())),
+    }
+}
+
+/// The non-null elements of `values`, which must be numbers.
+fn numeric_values(values: &[Object]) -> Result<Vec<&Object>, EvalError> {
+    let mut numbers = vec![];
+    for value in values {
+        match value {
+            Object::Int(_) | Object::Double(_) | Object::Decimal(_) | Object::BigInt(_) => {
+                numbers.push(value)
+            }
+            Object::Null => (),
+            _ => {
+                return Err(EvalError::ExpectedObjectXFoundY {
+                    expected: "number",
+                    found: value.clone(),
+                })
+            }
+        }
+    }
+    Ok(numbers)
+}
+
+/// Sorts numbers or strings in ascending order, nulls last.
+fn sort_values(values: &[Object]) -> Result<Object, EvalError> {
+    let mut sorted = values.to_vec();
+    let is_str = matches!(
+        values.iter().find(|value| **value != Object::Null),
+        Some(Object::Str(_))
+    );
+    if is_str {
+        if let Some(value) = values
+            .iter()
+            .find(|value| !matches!(value, Object::Str(_) | Object::Null))
+        {
+            return Err(EvalError::ExpectedObjectXFoundY {
+                expected: "string",
+                found: value.clone(),
+            });
+        }
+        sorted.sort_by(|left, right| match (left, right) {
+            (Object::Str(left), Object::Str(right)) => left.cmp(right),
+            _ => (*left == Object::Null).cmp(&(*right == Object::Null)),
+        });
+    } else {
+        numeric_values(values)?;
+        sorted.sort_by(|left, right| {
+            (*left == Object::Null)
+                .cmp(&(*right == Object::Null))
+                .then_with(|| compare_numbers(left, right))
         });
     }
+    Ok(Object::List(Rc::new(sorted)))
+}
 
-    let condition_evaluated_val = eval(arguments[0].as_ref(), context.clone())?;
-    let Object::Bool(is_true) = condition_evaluated_val else {
-        return Err(EvalError::ExpectedObjectXFoundY {
-            expected: "bool",
-            found: condition_evaluated_val,
+/// `map([items], x => x * 2)` or `filter([items], x => x > 10)`. `filter`
+/// keeps the elements for which the lambda gives true, dropping those for which
+/// it gives null.
+fn eval_map_or_filter_fn_call(
+    function_name: &str,
+    arguments: &[Box<Expression>],
+    context: Context,
+) -> Result<Object, EvalError> {
+    expect_arguments(2, arguments.len())?;
+    let (parameters, body) = lambda_argument(&arguments[1], 1)?;
+    let Some(list) = eval_list_argument(&arguments[0], &context)? else {
+        return Ok(Object::Null);
+    };
+    let mut results = vec![];
+    for element in list.iter() {
+        let result = call_lambda(parameters, body, vec![element.clone()], &context)?;
+        match (function_name, result) {
+            ("map", result) => results.push(result),
+            (_, Object::Bool(true)) => results.push(element.clone()),
+            (_, Object::Bool(false) | Object::Null) => (),
+            (_, found) => {
+                return Err(EvalError::ExpectedObjectXFoundY {
+                    expected: "bool",
+                    found,
+                })
+            }
+        }
+    }
+    Ok(Object::List(Rc::new(results)))
+}
+
+/// `reduce([items], 0, (acc, x) => acc + x)`.
+fn eval_reduce_fn_call(
+    arguments: &[Box<Expression>],
+    context: Context,
+) -> Result<Object, EvalError> {
+    expect_arguments(3, arguments.len())?;
+    let (parameters, body) = lambda_argument(&arguments[2], 2)?;
+    let Some(list) = eval_list_argument(&arguments[0], &context)? else {
+        return Ok(Object::Null);
+    };
+    let mut accumulator = eval(&arguments[1], context.clone())?;
+    for element in list.iter() {
+        accumulator = call_lambda(
+            parameters,
+            body,
+            vec![accumulator, element.clone()],
+            &context,
+        )?;
+    }
+    Ok(accumulator)
+}
+
+/// Evaluates the list a function works on, `None` standing for null.
+fn eval_list_argument(
+    argument: &Expression,
+    context: &Context,
+) -> Result<Option<Rc<Vec<Object>>>, EvalError> {
+    match eval(argument, context.clone())? {
+        Object::List(list) => Ok(Some(list)),
+        Object::Null => Ok(None),
+        found => Err(EvalError::ExpectedObjectXFoundY {
+            expected: "list",
+            found,
+        }),
+    }
+}
+
+fn lambda_argument(
+    argument: &Expression,
+    arity: u8,
+) -> Result<(&[Rc<String>], &Expression), EvalError> {
+    let Expression::Lambda { parameters, body } = argument else {
+        return Err(EvalError::ExpectedExpressionXFoundY {
+            expected: "lambda",
+            found: argument.clone(),
         });
     };
+    expect_arguments(arity, parameters.len())?;
+    Ok((parameters, body))
+}
+
+/// Evaluates `body` with each parameter bound to the matching value.
+fn call_lambda(
+    parameters: &[Rc<String>],
+    body: &Expression,
+    values: Vec<Object>,
+    context: &Context,
+) -> Result<Object, EvalError> {
+    let bindings: HashMap<String, Object> = parameters
+        .iter()
+        .map(|parameter| parameter.to_string())
+        .zip(values)
+        .collect();
+    eval(body, context.child(bindings))
+}
+
+fn expect_arguments(expected: u8, found: usize) -> Result<(), EvalError> {
+    match found == expected as usize {
+        true => Ok(()),
+        false => Err(EvalError::MissMatchFunctionArguements {
+            expected,
+            found: found as u8,
+        }),
+    }
+}
+
+/// A null condition picks the `false` branch, like a SQL `CASE`.
+fn eval_if_fn_call(arguments: &[Box<Expression>], context: Context) -> Result<Object, EvalError> {
+    expect_arguments(3, arguments.len())?;
+
+    let condition_evaluated_val = eval(arguments[0].as_ref(), context.clone())?;
+    let is_true = match condition_evaluated_val {
+        Object::Bool(is_true) => is_true,
+        Object::Null => false,
+        _ => {
+            return Err(EvalError::ExpectedObjectXFoundY {
+                expected: "bool",
+                found: condition_evaluated_val,
+            })
+        }
+    };
 
     match is_true {
         true => eval(arguments[1].as_ref(), context.clone()),
@@ -114,6 +600,31 @@ fn eval_fn_call(
     }
 }
 
+fn eval_ifnull_fn_call(
+    arguments: &[Box<Expression>],
+    context: Context,
+) -> Result<Object, EvalError> {
+    expect_arguments(2, arguments.len())?;
+    match eval(arguments[0].as_ref(), context.clone())? {
+        Object::Null => eval(arguments[1].as_ref(), context),
+        value => Ok(value),
+    }
+}
+
+/// Returns the first argument that is not null, without evaluating the rest.
+fn eval_coalesce_fn_call(
+    arguments: &[Box<Expression>],
+    context: Context,
+) -> Result<Object, EvalError> {
+    for argument in arguments {
+        match eval(argument, context.clone())? {
+            Object::Null => continue,
+            value => return Ok(value),
+        }
+    }
+    Ok(Object::Null)
+}
+
 fn eval_numeric_infix_expression(
     operator: Token,
     left: &Expression,
@@ -122,32 +633,270 @@ fn eval_numeric_infix_expression(
 ) -> Result<Object, EvalError> {
     let left = eval(left, context.clone())?;
     let right = eval(right, context.clone())?;
-    let (left, right) = match operator.clone() {
-        Token::Plus
-        | Token::Minus
-        | Token::Asterisk
-        | Token::Slash
-        | Token::LessThan
-        | Token::LessThanEqualTo
-        | Token::GreaterThan
-        | Token::GreaterThanEqualTo
-        | Token::Percent => match (&left, &right) {
-            (Object::Int(left), Object::Int(right)) => (*left as f64, *right as f64),
-            (Object::Double(left), Object::Double(right)) => (*left, *right),
-            (Object::Double(left), Object::Int(right)) => (*left, *right as f64),
-            (Object::Int(left), Object::Double(right)) => (*left as f64, *right),
-            _ => {
-                return Err(EvalError::UnExpectedOperatorOperandFound {
+    if left == Object::Null || right == Object::Null {
+        return Ok(Object::Null);
+    }
+    let options = context.options();
+    let is_division = matches!(operator, Token::Slash | Token::DoubleSlash | Token::Percent);
+    let is_zero = match right {
+        Object::Int(int) => int == 0,
+        Object::Double(double) => double == 0.0,
+        Object::Decimal(decimal) => decimal.is_zero(),
+        Object::BigInt(ref int) => int.is_zero(),
+        _ => false,
+    };
+    if is_division && is_zero {
+        match options.division_by_zero {
+            DivisionByZeroPolicy::Ieee => (),
+            DivisionByZeroPolicy::Error => return Err(EvalError::DivideByZero),
+            DivisionByZeroPolicy::Null => return Ok(Object::Null),
+        }
+    }
+    apply_numeric_operator(operator, left, right, options)
+}
+
+/// Two numbers brought to a common type: decimals when either one is a
+/// decimal, so that mixing them with doubles keeps their precision, big
+/// integers for a big integer and an integer, and doubles otherwise.
+enum NumericOperands {
+    Float(f64, f64),
+    Decimal(Decimal, Decimal),
+    BigInt(BigInt, BigInt),
+}
+
+fn numeric_operands(left: &Object, right: &Object) -> Option<NumericOperands> {
+    let as_float = |value: &Object| match value {
+        Object::Int(int) => Some(*int as f64),
+        Object::Double(double) => Some(*double),
+        Object::BigInt(int) => int.to_f64(),
+        _ => None,
+    };
+    // NaN, infinities and numbers beyond the range of a decimal have no
+    // decimal value.
+    let as_decimal = |value: &Object| match value {
+        Object::Int(int) => Some(Decimal::from(*int)),
+        Object::Double(double) => Decimal::try_from(*double).ok(),
+        Object::Decimal(decimal) => Some(*decimal),
+        Object::BigInt(int) => int
+            .to_i128()
+            .and_then(|int| Decimal::try_from_i128_with_scale(int, 0).ok()),
+        _ => None,
+    };
+    let as_big_int = |value: &Object| match value {
+        Object::Int(int) => Some(BigInt::from(*int)),
+        Object::BigInt(int) => Some(int.as_ref().clone()),
+        _ => None,
+    };
+    match (left, right) {
+        (Object::Decimal(_), _) | (_, Object::Decimal(_)) => Some(NumericOperands::Decimal(
+            as_decimal(left)?,
+            as_decimal(right)?,
+        )),
+        (Object::BigInt(_), Object::Int(_) | Object::BigInt(_))
+        | (Object::Int(_), Object::BigInt(_)) => Some(NumericOperands::BigInt(
+            as_big_int(left)?,
+            as_big_int(right)?,
+        )),
+        _ => Some(NumericOperands::Float(as_float(left)?, as_float(right)?)),
+    }
+}
+
+/// Orders two numbers, NaN coming after every other double.
+fn compare_numbers(left: &Object, right: &Object) -> Ordering {
+    if let (Object::Int(left), Object::Int(right)) = (left, right) {
+        return left.cmp(right);
+    }
+    match numeric_operands(left, right) {
+        Some(NumericOperands::Float(left, right)) => left.total_cmp(&right),
+        Some(NumericOperands::Decimal(left, right)) => left.cmp(&right),
+        Some(NumericOperands::BigInt(left, right)) => left.cmp(&right),
+        None => Ordering::Equal,
+    }
+}
+
+/// Applies an arithmetic or comparison operator to two non-null operands.
+fn apply_numeric_operator(
+    operator: Token,
+    left: Object,
+    right: Object,
+    options: EvalOptions,
+) -> Result<Object, EvalError> {
+    if let (Object::Int(left), Object::Int(right)) = (&left, &right) {
+        if let Some(result) =
+            apply_operator_to_int_values(&operator, *left, *right, options.promote_to_big_int)
+        {
+            return result;
+        }
+    }
+    let is_division = matches!(operator, Token::Slash | Token::DoubleSlash | Token::Percent);
+    let operands = match (&left, &right) {
+        // An operator whose result did not fit in an int, which is exact with
+        // big integers.
+        (Object::Int(left), Object::Int(right))
+            if options.promote_to_big_int && operator != Token::Slash =>
+        {
+            Some(NumericOperands::BigInt(
+                BigInt::from(*left),
+                BigInt::from(*right),
+            ))
+        }
+        _ => numeric_operands(&left, &right),
+    };
+    match operands {
+        Some(NumericOperands::Float(left, right)) => check_finite(
+            apply_operator_to_float_values(operator, left, right),
+            options,
+        ),
+        // Decimals have no infinity to give instead.
+        Some(NumericOperands::Decimal(_, right)) if is_division && right.is_zero() => {
+            Err(EvalError::DivideByZero)
+        }
+        Some(NumericOperands::Decimal(left_decimal, right_decimal)) => {
+            match apply_operator_to_decimal_values(&operator, left_decimal, right_decimal) {
+                Some(result) => check_finite(result, options),
+                None => Err(EvalError::ArithmeticOverflow {
                     operator,
                     left,
                     right,
-                })
+                }),
             }
-        },
+        }
+        Some(NumericOperands::BigInt(_, right))
+            if matches!(operator, Token::DoubleSlash | Token::Percent) && right.is_zero() =>
+        {
+            Err(EvalError::DivideByZero)
+        }
+        Some(NumericOperands::BigInt(left_int, right_int)) => {
+            match apply_operator_to_big_int_values(&operator, left_int, right_int) {
+                Some(result) => check_finite(result, options),
+                None => Err(EvalError::ArithmeticOverflow {
+                    operator,
+                    left,
+                    right,
+                }),
+            }
+        }
+        None => Err(EvalError::UnExpectedOperatorOperandFound {
+            operator,
+            left,
+            right,
+        }),
+    }
+}
+
+/// The operators whose result stays an integer for integer operands, or
+/// `None` to compute the result as a double.
+///
+/// `//` and `%` truncate toward zero as in C#, so that `a == a // b * b + a % b`
+/// and the remainder has the sign of `a`: `-7 // 2` is `-3` and `-7 % 2` is
+/// `-1`. A power is only an integer when the exponent is not negative and the
+/// result fits. With `promote_to_big_int`, `+`, `-` and `*` keep integers as
+/// well.
+///
+/// Comparisons are exact, as doubles cannot tell apart ints beyond 2^53.
+fn apply_operator_to_int_values(
+    operator: &Token,
+    left: i64,
+    right: i64,
+    promote_to_big_int: bool,
+) -> Option<Result<Object, EvalError>> {
+    let int = |result: Option<i64>| result.map(|int| Ok(Object::Int(int)));
+    match operator {
+        Token::DoubleSlash | Token::Percent if right == 0 => Some(Err(EvalError::DivideByZero)),
+        Token::Plus if promote_to_big_int => int(left.checked_add(right)),
+        Token::Minus if promote_to_big_int => int(left.checked_sub(right)),
+        Token::Asterisk if promote_to_big_int => int(left.checked_mul(right)),
+        // Only `i64::MIN // -1` does not fit.
+        Token::DoubleSlash => int(left.checked_div(right)),
+        Token::Percent => int(Some(left.wrapping_rem(right))),
+        Token::DoubleAsterisk => int(u32::try_from(right)
+            .ok()
+            .and_then(|exponent| left.checked_pow(exponent))),
+        Token::LessThan => Some(Ok(Object::Bool(left < right))),
+        Token::LessThanEqualTo => Some(Ok(Object::Bool(left <= right))),
+        Token::GreaterThan => Some(Ok(Object::Bool(left > right))),
+        Token::GreaterThanEqualTo => Some(Ok(Object::Bool(left >= right))),
+        Token::Equals => Some(Ok(Object::Bool(left == right))),
+        Token::NotEquals | Token::NotEqualsAngleBrackets => Some(Ok(Object::Bool(left != right))),
+        _ => None,
+    }
+}
+
+/// Powers of big integers whose result would need more bits than this are
+/// reported as an overflow rather than computed.
+const MAX_BIG_INT_POWER_BITS: u64 = 1 << 20;
+
+/// `None` when a power is too large to compute. `/` and powers with a negative
+/// exponent are not exact, so they give doubles.
+fn apply_operator_to_big_int_values(
+    operator: &Token,
+    left: BigInt,
+    right: BigInt,
+) -> Option<Object> {
+    match operator {
+        Token::Plus => Some(big_int_to_object(left + right)),
+        Token::Minus => Some(big_int_to_object(left - right)),
+        Token::Asterisk => Some(big_int_to_object(left * right)),
+        Token::Slash => Some(Object::Double(left.to_f64()? / right.to_f64()?)),
+        // Both truncate toward zero, as for ints.
+        Token::DoubleSlash => Some(big_int_to_object(left / right)),
+        Token::Percent => Some(big_int_to_object(left % right)),
+        Token::DoubleAsterisk if right.is_negative() => {
+            Some(Object::Double(left.to_f64()?.powf(right.to_f64()?)))
+        }
+        Token::DoubleAsterisk => {
+            let exponent = right.to_u64()?;
+            // 0, 1 and -1 need no more bits whatever the exponent.
+            let bits = left.bits().saturating_sub(1).checked_mul(exponent)?;
+            (bits <= MAX_BIG_INT_POWER_BITS).then(|| big_int_to_object(Pow::pow(left, exponent)))
+        }
+        Token::LessThan => Some(Object::Bool(left < right)),
+        Token::LessThanEqualTo => Some(Object::Bool(left <= right)),
+        Token::GreaterThan => Some(Object::Bool(left > right)),
+        Token::GreaterThanEqualTo => Some(Object::Bool(left >= right)),
+        Token::Equals => Some(Object::Bool(left == right)),
+        Token::NotEquals | Token::NotEqualsAngleBrackets => Some(Object::Bool(left != right)),
         _ => unimplemented!(),
-    };
+    }
+}
 
-    Ok(apply_operator_to_float_values(operator, left, right))
+/// An `Int` when `int` fits in one, as the lexer reads integer literals.
+fn big_int_to_object(int: BigInt) -> Object {
+    match int.to_i64() {
+        Some(int) => Object::Int(int),
+        None => Object::BigInt(Rc::new(int)),
+    }
+}
+
+/// `None` when the result does not fit in a decimal. A decimal raised to a
+/// fractional power is only approximate, so it is computed as a double.
+fn apply_operator_to_decimal_values(
+    operator: &Token,
+    left: Decimal,
+    right: Decimal,
+) -> Option<Object> {
+    let decimal = |result: Option<Decimal>| result.map(Object::Decimal);
+    match operator {
+        Token::Plus => decimal(left.checked_add(right)),
+        Token::Minus => decimal(left.checked_sub(right)),
+        Token::Asterisk => decimal(left.checked_mul(right)),
+        Token::Slash => decimal(left.checked_div(right)),
+        Token::DoubleSlash => decimal(left.checked_div(right).map(|quotient| quotient.trunc())),
+        Token::Percent => decimal(left.checked_rem(right)),
+        Token::DoubleAsterisk if right.fract().is_zero() => decimal(
+            right
+                .to_i64()
+                .and_then(|exponent| left.checked_powi(exponent)),
+        ),
+        Token::DoubleAsterisk => Some(Object::Double(left.to_f64()?.powf(right.to_f64()?))),
+        Token::LessThan => Some(Object::Bool(left < right)),
+        Token::LessThanEqualTo => Some(Object::Bool(left <= right)),
+        Token::GreaterThan => Some(Object::Bool(left > right)),
+        Token::GreaterThanEqualTo => Some(Object::Bool(left >= right)),
+        Token::Equals => Some(Object::Bool(left == right)),
+        Token::NotEquals | Token::NotEqualsAngleBrackets => Some(Object::Bool(left != right)),
+        _ => unimplemented!(),
+    }
 }
 
 fn apply_operator_to_float_values(operator: Token, left: f64, right: f64) -> Object {
@@ -156,13 +905,15 @@ fn apply_operator_to_float_values(operator: Token, left: f64, right: f64) -> Obj
         Token::Minus => Object::Double(left - right),
         Token::Asterisk => Object::Double(left * right),
         Token::Slash => Object::Double(left / right),
+        Token::DoubleSlash => Object::Double((left / right).trunc()),
         Token::LessThan => Object::Bool(left < right),
         Token::LessThanEqualTo => Object::Bool(left <= right),
         Token::GreaterThan => Object::Bool(left > right),
         Token::GreaterThanEqualTo => Object::Bool(left >= right),
         Token::Equals => Object::Bool(left == right),
-        Token::NotEquals => Object::Bool(left != right),
+        Token::NotEquals | Token::NotEqualsAngleBrackets => Object::Bool(left != right),
         Token::Percent => Object::Double(left % right),
+        Token::DoubleAsterisk => Object::Double(left.powf(right)),
         _ => unimplemented!(),
     }
 }
@@ -175,40 +926,60 @@ fn eval_infix_expression_where_operand_can_be_numerics_or_bools(
 ) -> Result<Object, EvalError> {
     let left = eval(left, context.clone())?;
     let right = eval(right, context.clone())?;
+    compare_objects(operator, left, right, context.options())
+}
+
+/// Applies `==`, `!=` or `<>` to two values.
+fn compare_objects(
+    operator: Token,
+    left: Object,
+    right: Object,
+    options: EvalOptions,
+) -> Result<Object, EvalError> {
     match (&left, &right) {
-        (Object::Int(left), Object::Int(right)) => {
-            let left = *left as f64;
-            let right = *right as f64;
-            Ok(apply_operator_to_float_values(operator, left, right))
-        }
-        (Object::Double(left), Object::Double(right)) => {
-            let left = *left;
-            let right = *right;
-            Ok(apply_operator_to_float_values(operator, left, right))
-        }
-        (Object::Double(left), Object::Int(right)) => {
-            let left = *left;
-            let right = *right as f64;
-            Ok(apply_operator_to_float_values(operator, left, right))
-        }
-        (Object::Int(left), Object::Double(right)) => {
-            let left = *left as f64;
-            let right = *right;
-            Ok(apply_operator_to_float_values(operator, left, right))
-        }
-        (Object::Bool(left), Object::Bool(right)) => match operator {
+        (Object::Null, _) | (_, Object::Null) => Ok(Object::Null),
+        (
+            Object::Int(_) | Object::Double(_) | Object::Decimal(_) | Object::BigInt(_),
+            Object::Int(_) | Object::Double(_) | Object::Decimal(_) | Object::BigInt(_),
+        ) => apply_numeric_operator(operator, left, right, options),
+        (Object::Bool(_), Object::Bool(_)) | (Object::Str(_), Object::Str(_)) => match operator {
             Token::Equals => Ok(Object::Bool(left == right)),
-            Token::NotEquals => Ok(Object::Bool(left != right)),
-            Token::Or | Token::DoublePipe => Ok(Object::Bool(*left || *right)),
-            _ => todo!(),
-        },
-        _ => {
-            return Err(EvalError::UnExpectedOperatorOperandFound {
+            Token::NotEquals | Token::NotEqualsAngleBrackets => Ok(Object::Bool(left != right)),
+            _ => Err(EvalError::UnExpectedOperatorOperandFound {
                 operator,
                 left,
                 right,
-            })
+            }),
+        },
+        // Lists are equal when their elements are, null elements making the
+        // result null unless some other pair differs.
+        (Object::List(left), Object::List(right)) => {
+            let mut equal = Object::Bool(left.len() == right.len());
+            if equal == Object::Bool(true) {
+                for (left, right) in left.iter().zip(right.iter()) {
+                    match compare_objects(Token::Equals, left.clone(), right.clone(), options)? {
+                        Object::Bool(false) => {
+                            equal = Object::Bool(false);
+                            break;
+                        }
+                        Object::Null => equal = Object::Null,
+                        _ => (),
+                    }
+                }
+            }
+            match (operator, equal) {
+                (Token::Equals, equal) => Ok(equal),
+                (Token::NotEquals | Token::NotEqualsAngleBrackets, Object::Bool(equal)) => {
+                    Ok(Object::Bool(!equal))
+                }
+                (_, equal) => Ok(equal),
+            }
         }
+        _ => Err(EvalError::UnExpectedOperatorOperandFound {
+            operator,
+            left,
+            right,
+        }),
     }
 }
 
@@ -223,24 +994,403 @@ fn eval_infix_expression(
         | Token::Minus
         | Token::Asterisk
         | Token::Slash
+        | Token::DoubleSlash
         | Token::LessThan
         | Token::LessThanEqualTo
         | Token::GreaterThan
         | Token::GreaterThanEqualTo
-        | Token::Percent => eval_numeric_infix_expression(operator, left, right, context),
-        Token::Equals | Token::NotEquals | Token::DoublePipe | Token::Or => {
+        | Token::Percent
+        | Token::DoubleAsterisk => eval_numeric_infix_expression(operator, left, right, context),
+        Token::Equals | Token::NotEquals | Token::NotEqualsAngleBrackets => {
             eval_infix_expression_where_operand_can_be_numerics_or_bools(
                 operator, left, right, context,
             )
         }
+        Token::Or | Token::DoublePipe | Token::And | Token::DoubleAmpersand => {
+            eval_logical_infix_expression(operator, left, right, context)
+        }
+        Token::In | Token::NotIn => eval_in_expression(operator, left, right, context),
+        // The right operand is only evaluated when the left one is null.
+        Token::DoubleQuestionMark => match eval(left, context.clone())? {
+            Object::Null => eval(right, context),
+            left => Ok(left),
+        },
         _ => Err(EvalError::UnExpectedTokenFound { token: operator }),
     }
 }
 
+/// `x in (a, b)` is true when `x == a` or `x == b` is. When nothing matches but
+/// a comparison gave null the result is null, as in SQL.
+///
+/// The right operand is either a list written in place, whose elements after
+/// the first match are not evaluated, or any expression giving a list.
+fn eval_in_expression(
+    operator: Token,
+    left: &Expression,
+    right: &Expression,
+    context: Context,
+) -> Result<Object, EvalError> {
+    let value = eval(left, context.clone())?;
+    let list;
+    let elements: Box<dyn Iterator<Item = Result<Object, EvalError>>> = match right {
+        Expression::List(elements) => Box::new(
+            elements
+                .iter()
+                .map(|element| eval(element, context.clone())),
+        ),
+        right => match eval(right, context.clone())? {
+            Object::List(objects) => {
+                list = objects;
+                Box::new(list.iter().cloned().map(Ok))
+            }
+            Object::Null => return Ok(Object::Null),
+            found => {
+                return Err(EvalError::ExpectedObjectXFoundY {
+                    expected: "list",
+                    found,
+                })
+            }
+        },
+    };
+    let mut found = Object::Bool(false);
+    for element in elements {
+        match compare_objects(Token::Equals, value.clone(), element?, context.options())? {
+            Object::Bool(true) => {
+                found = Object::Bool(true);
+                break;
+            }
+            Object::Null => found = Object::Null,
+            _ => (),
+        }
+    }
+    match (operator, found) {
+        (Token::NotIn, Object::Bool(found)) => Ok(Object::Bool(!found)),
+        (_, found) => Ok(found),
+    }
+}
+
+/// `and` and `or` short-circuit: the right operand is only evaluated when the
+/// left one does not already decide the result.
+///
+/// Null stands for an unknown bool, as in SQL: `null and false` is `false`,
+/// `null or true` is `true`, and any other combination with null is null.
+fn eval_logical_infix_expression(
+    operator: Token,
+    left: &Expression,
+    right: &Expression,
+    context: Context,
+) -> Result<Object, EvalError> {
+    let short_circuit_on = matches!(operator, Token::Or | Token::DoublePipe);
+    let left = eval_logical_operand(left, context.clone())?;
+    if left == Some(short_circuit_on) {
+        return Ok(Object::Bool(short_circuit_on));
+    }
+    match (left, eval_logical_operand(right, context)?) {
+        (_, Some(right)) if right == short_circuit_on => Ok(Object::Bool(right)),
+        (Some(_), Some(right)) => Ok(Object::Bool(right)),
+        _ => Ok(Object::Null),
+    }
+}
+
+/// Evaluates an operand of a logical operator, `None` standing for null.
+fn eval_logical_operand(
+    expression: &Expression,
+    context: Context,
+) -> Result<Option<bool>, EvalError> {
+    match eval(expression, context)? {
+        Object::Bool(value) => Ok(Some(value)),
+        Object::Null => Ok(None),
+        value => Err(EvalError::ExpectedObjectXFoundY {
+            expected: "bool",
+            found: value,
+        }),
+    }
+}
+
+fn eval_index_expression(
+    expression: &Expression,
+    index: &Expression,
+    context: Context,
+) -> Result<Object, EvalError> {
+    let list = match eval(expression, context.clone())? {
+        Object::List(list) => list,
+        Object::Null => return Ok(Object::Null),
+        found => {
+            return Err(EvalError::ExpectedObjectXFoundY {
+                expected: "list",
+                found,
+            })
+        }
+    };
+    // Arithmetic gives doubles or decimals, so whole ones are accepted as well.
+    let index = match eval(index, context)? {
+        Object::Int(index) => index,
+        Object::Double(index) if index.fract() == 0.0 => index as i64,
+        Object::Decimal(index) if index.fract().is_zero() => index.to_i64().unwrap_or(i64::MAX),
+        // Beyond i64, so beyond the end of any list.
+        Object::BigInt(index) if index.is_negative() => i64::MIN,
+        Object::BigInt(_) => i64::MAX,
+        Object::Null => return Ok(Object::Null),
+        found => {
+            return Err(EvalError::ExpectedObjectXFoundY {
+                expected: "int",
+                found,
+            })
+        }
+    };
+    usize::try_from(index)
+        .ok()
+        .and_then(|position| list.get(position))
+        .cloned()
+        .ok_or(EvalError::IndexOutOfBounds {
+            index,
+            len: list.len(),
+        })
+}
+
+/// Looks `member` up in the map `map` evaluates to. Like a missing parameter, a
+/// missing key is an error unless the options make it null, or the access is
+/// written `?.member`.
+///
+/// On any other value, `.member` calls the function of that name without
+/// further arguments, so that `[name].Length` is `Length([name])`.
+fn eval_member_expression(
+    expression: &Expression,
+    map: &Expression,
+    member: &str,
+    null_safe: bool,
+    context: Context,
+) -> Result<Object, EvalError> {
+    let options = context.options();
+    let map = match eval(map, context)? {
+        Object::Map(map) => map,
+        Object::Null => return Ok(Object::Null),
+        receiver => {
+            return match call_builtin_function(member, vec![receiver.clone()], options) {
+                // Not a function either: most likely a member of the wrong value.
+                Err(EvalError::UnknownFunction(_)) => Err(EvalError::ExpectedObjectXFoundY {
+                    expected: "map",
+                    found: receiver,
+                }),
+                result => result,
+            };
+        }
+    };
+    match (map.get(member), options.missing_parameter) {
+        (Some(object), _) => Ok(object.clone()),
+        (None, MissingParameterPolicy::Error) if !null_safe => {
+            Err(EvalError::MemberNotFound(expression.to_string()))
+        }
+        (None, _) => Ok(Object::Null),
+    }
+}
+
+/// Concatenates the text of a template with its values. Null values are
+/// written as nothing, as in .NET.
+fn eval_template(parts: &[TemplatePart], context: Context) -> Result<Object, EvalError> {
+    let mut result = String::new();
+    for part in parts {
+        match part {
+            TemplatePart::Text(text) => result.push_str(text),
+            TemplatePart::Value { expression, format } => {
+                let value = eval(expression, context.clone())?;
+                result.push_str(&format_value(value, format.as_ref().map(|f| f.as_str()))?);
+            }
+        }
+    }
+    Ok(Object::Str(Rc::new(result)))
+}
+
+fn format_value(value: Object, format: Option<&str>) -> Result<String, EvalError> {
+    match (value, format) {
+        (Object::Null, _) => Ok(String::new()),
+        (Object::Int(int), None) => Ok(int.to_string()),
+        (Object::Double(double), None) => Ok(double.to_string()),
+        (Object::Decimal(decimal), None) => Ok(decimal.to_string()),
+        (Object::BigInt(int), None) => Ok(int.to_string()),
+        (Object::Bool(bool), None) => Ok(bool.to_string()),
+        (Object::Str(string), None) => Ok(string.to_string()),
+        (
+            value @ (Object::Int(_) | Object::Double(_) | Object::Decimal(_) | Object::BigInt(_)),
+            Some(format),
+        ) => format_number(&value, format).ok_or_else(|| EvalError::InvalidFormatSpecifier {
+            format: format.to_string(),
+            found: value,
+        }),
+        (found, Some(_)) => Err(EvalError::ExpectedObjectXFoundY {
+            expected: "number",
+            found,
+        }),
+        (found, None) => Err(EvalError::ExpectedObjectXFoundY {
+            expected: "number, string or bool",
+            found,
+        }),
+    }
+}
+
+/// Formats a number for a .NET style format specifier: `F` (fixed point), `N`
+/// (fixed point with thousands separators) or `D` (zero padded integer), each
+/// with an optional precision as in `N2`, or a custom pattern such as
+/// `#,##0.00`. `None` when the specifier is not supported.
+fn format_number(value: &Object, format: &str) -> Option<String> {
+    let integer = match value {
+        Object::Int(int) => int.to_string(),
+        Object::BigInt(int) => int.to_string(),
+        Object::Double(double) if !double.is_finite() => return Some(double.to_string()),
+        Object::Double(_) | Object::Decimal(_) => String::new(),
+        _ => return None,
+    };
+    // The number written with `precision` decimal places. Doubles and
+    // decimals are rounded half away from zero, as .NET does, and integers
+    // are written exactly.
+    let fixed = |precision: usize| match value {
+        Object::Double(double) => format_double_fixed(*double, precision),
+        Object::Decimal(decimal) => {
+            let rounded = decimal
+                .round_dp_with_strategy(precision as u32, RoundingStrategy::MidpointAwayFromZero);
+            format!("{:.*}", precision, rounded)
+        }
+        _ if precision == 0 => integer.clone(),
+        _ => format!("{}.{}", integer, "0".repeat(precision)),
+    };
+    let mut chars = format.chars();
+    let specifier = chars.next()?;
+    if !specifier.is_ascii_alphabetic() {
+        return format_custom_number(fixed, format);
+    }
+    let precision = match chars.as_str() {
+        "" => None,
+        digits if digits.len() <= 2 && digits.bytes().all(|digit| digit.is_ascii_digit()) => {
+            digits.parse::<usize>().ok()
+        }
+        _ => return None,
+    };
+    match (specifier.to_ascii_uppercase(), value) {
+        ('F', _) => Some(fixed(precision.unwrap_or(2))),
+        ('N', _) => Some(group_thousands(&fixed(precision.unwrap_or(2)))),
+        ('D', Object::Int(_) | Object::BigInt(_)) => {
+            let (sign, digits) = match integer.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", integer.as_str()),
+            };
+            let width = precision.unwrap_or(0);
+            Some(format!("{}{:0>width$}", sign, digits))
+        }
+        _ => None,
+    }
+}
+
+/// Writes a finite `double` with `precision` decimal places, rounding half
+/// away from zero where `format!` would round half to even.
+fn format_double_fixed(double: f64, precision: usize) -> String {
+    // No double has more than 1074 decimal places, so written out in full it
+    // is halfway between two results when the digits dropped are a 5 followed
+    // by zeros only.
+    let exact = format!("{:.1074}", double.abs());
+    let (integer, fraction) = exact.split_once('.').unwrap_or((&exact, ""));
+    let is_midpoint = fraction
+        .get(precision..)
+        .and_then(|dropped| dropped.strip_prefix('5'))
+        .is_some_and(|rest| rest.bytes().all(|digit| digit == b'0'));
+    if !is_midpoint {
+        return format!("{:.*}", precision, double);
+    }
+    let mut digits = format!("{}{}", integer, &fraction[..precision]).into_bytes();
+    // Adds one to the last digit kept.
+    let mut position = digits.len();
+    loop {
+        if position == 0 {
+            digits.insert(0, b'1');
+            break;
+        }
+        position -= 1;
+        match digits[position] {
+            b'9' => digits[position] = b'0',
+            digit => {
+                digits[position] = digit + 1;
+                break;
+            }
+        }
+    }
+    let digits = String::from_utf8_lossy(&digits);
+    let (integer, fraction) = digits.split_at(digits.len() - precision);
+    let sign = if double.is_sign_negative() { "-" } else { "" };
+    match precision {
+        0 => format!("{}{}", sign, integer),
+        _ => format!("{}{}.{}", sign, integer, fraction),
+    }
+}
+
+/// Formats a number for a pattern where `0` is a digit that is always
+/// written, `#` a digit that is only written when significant, `.` the
+/// decimal point, and a `,` before it turns on thousands separators.
+fn format_custom_number(fixed: impl Fn(usize) -> String, format: &str) -> Option<String> {
+    let (integer_format, fraction_format) = format.split_once('.').unwrap_or((format, ""));
+    let is_valid = integer_format
+        .chars()
+        .all(|ch| matches!(ch, '0' | '#' | ','))
+        && fraction_format.chars().all(|ch| matches!(ch, '0' | '#'));
+    if !is_valid {
+        return None;
+    }
+    let formatted = fixed(fraction_format.len());
+    let (is_negative, formatted) = match formatted.strip_prefix('-') {
+        Some(formatted) => (true, formatted),
+        None => (false, formatted.as_str()),
+    };
+    let (integer, fraction) = formatted.split_once('.').unwrap_or((formatted, ""));
+    let integer = format!(
+        "{:0>width$}",
+        integer.trim_start_matches('0'),
+        width = integer_format.matches('0').count()
+    );
+    let integer = match integer_format.contains(',') {
+        true => group_thousands(&integer),
+        false => integer,
+    };
+    let mut fraction = fraction.trim_end_matches('0').to_string();
+    while fraction.len() < fraction_format.matches('0').count() {
+        fraction.push('0');
+    }
+    let is_zero = !integer
+        .chars()
+        .chain(fraction.chars())
+        .any(|ch| matches!(ch, '1'..='9'));
+    let sign = if is_negative && !is_zero { "-" } else { "" };
+    Some(match fraction.is_empty() {
+        true => format!("{}{}", sign, integer),
+        false => format!("{}{}.{}", sign, integer, fraction),
+    })
+}
+
+/// Inserts a `,` between each group of three digits of the integer part of a
+/// formatted number.
+fn group_thousands(number: &str) -> String {
+    let (sign, number) = match number.strip_prefix('-') {
+        Some(number) => ("-", number),
+        None => ("", number),
+    };
+    let (integer, fraction) = number.split_at(number.find('.').unwrap_or(number.len()));
+    let mut grouped = String::from(sign);
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped + fraction
+}
+
 fn eval_ident(ident: &Rc<String>, context: Context) -> Result<Object, EvalError> {
-    match context.get(ident.as_ref()) {
-        Some(object) => Ok(object),
-        None => Err(EvalError::ReferenceNotFound(ident.as_ref().to_owned())),
+    match (
+        context.get(ident.as_ref()),
+        context.options().missing_parameter,
+    ) {
+        (Some(object), _) => Ok(object),
+        (None, MissingParameterPolicy::Null) => Ok(Object::Null),
+        (None, MissingParameterPolicy::Error) => {
+            Err(EvalError::ReferenceNotFound(ident.as_ref().to_owned()))
+        }
     }
 }
 
@@ -249,11 +1399,22 @@ fn eval_prefix_expression(
     expression: &Expression,
     context: Context,
 ) -> Result<Object, EvalError> {
+    let options = context.options();
     let value = eval(expression, context)?;
+    if value == Object::Null {
+        return Ok(Object::Null);
+    }
     match operator {
         Token::Minus => match value {
-            Object::Int(val) => Ok(Object::Int(-1 * val)),
-            Object::Double(val) => Ok(Object::Double(-1 as f64 * val)),
+            Object::Int(val) => Ok(match val.checked_neg() {
+                Some(val) => Object::Int(val),
+                // Only `-i64::MIN` does not fit.
+                None if options.promote_to_big_int => big_int_to_object(-BigInt::from(val)),
+                None => Object::Double(-(val as f64)),
+            }),
+            Object::Double(val) => Ok(Object::Double(-val)),
+            Object::Decimal(val) => Ok(Object::Decimal(-val)),
+            Object::BigInt(val) => Ok(big_int_to_object(-val.as_ref())),
             _ => Err(EvalError::ExpectedObjectXFoundY {
                 expected: "number",
                 found: value,
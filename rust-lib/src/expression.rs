@@ -1,15 +1,24 @@
 use std::rc::Rc;
 
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
 use crate::token::Token;
 
+#[allow(clippy::enum_variant_names, clippy::vec_box)]
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expression {
     Nil, // there was no input
     Int(i64),
     Double(f64),
+    Decimal(Decimal),
+    BigInt(Rc<BigInt>), // an integer beyond i64
     Bool(bool),
-    Ident(Rc<String>),
+    Ident(Rc<String>),     // [name], or the name of a called function
+    BareIdent(Rc<String>), // name, written without brackets
     Str(Rc<String>),
+    Null,
     PrefixExpression {
         operator: Token,
         expression: Box<Expression>,
@@ -24,6 +33,69 @@ pub enum Expression {
         // Had it been a case of full-fledged language, it could have been an inline function.
         arguments: Vec<Box<Expression>>,
     },
+    List(Vec<Box<Expression>>), // {a, b, c}, or (a, b, c) on the right of `in`
+    IndexExpression {
+        expression: Box<Expression>,
+        index: Box<Expression>,
+    },
+    MemberExpression {
+        expression: Box<Expression>,
+        member: Rc<String>, // expression.member, or expression?.member when null_safe
+        #[cfg_attr(feature = "json", serde(default))]
+        null_safe: bool,
+    },
+    Let {
+        name: Rc<String>, // let name = value in body
+        value: Box<Expression>,
+        body: Box<Expression>,
+    },
+    Lambda {
+        parameters: Vec<Rc<String>>, // x => ..., or (a, b) => ...
+        body: Box<Expression>,
+    },
+    Template(Vec<TemplatePart>), // $"text {expression:format}"
+}
+
+/// A piece of a `$"..."` template literal.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub enum TemplatePart {
+    Text(Rc<String>),
+    Value {
+        expression: Box<Expression>,
+        format: Option<Rc<String>>,
+    },
+}
+
+/// `[target] = value`, one statement of a [`Script`].
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct Assignment {
+    pub target: Rc<String>,
+    pub value: Expression,
+}
+
+/// Assignments separated by `;`, executed in order.
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct Script {
+    pub statements: Vec<Assignment>,
+}
+
+/// Byte offsets of a piece of the source, `end` being exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Spans of an [`Expression`] and, in the same order as they appear in the
+/// expression, of its subexpressions. The callee of a `CallExpression` comes
+/// before its arguments.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SpanTree {
+    pub span: Span,
+    pub children: Vec<SpanTree>,
 }
 
 #[derive(Debug)]
@@ -37,28 +109,39 @@ pub enum ParseExpressionError {
     UnexpectedToken {
         token: Token,
     },
+    ExpectedLambdaParameter {
+        found: Expression,
+    },
 }
 
 pub(crate) enum Precedence {
     Lowest,
+    NullCoalescing, // ??
+    LogicalOr,      // or, ||
+    LogicalAnd,     // and, &&
     Equals,
     LessGreater,
     Sum,
     Product,
-    Prefix, // -x or !x
-    Call,   // mufunc()
+    Exponent, // **, which is right associative
+    Prefix,   // -x or !x
+    Call,     // mufunc()
 }
 
 impl Precedence {
     pub(crate) fn value(&self) -> i32 {
         match self {
             Precedence::Lowest => 0,
-            Precedence::Equals => 1,
-            Precedence::LessGreater => 2,
-            Precedence::Sum => 3,
-            Precedence::Product => 4,
-            Precedence::Prefix => 5,
-            Precedence::Call => 6,
+            Precedence::NullCoalescing => 1,
+            Precedence::LogicalOr => 2,
+            Precedence::LogicalAnd => 3,
+            Precedence::Equals => 4,
+            Precedence::LessGreater => 5,
+            Precedence::Sum => 6,
+            Precedence::Product => 7,
+            Precedence::Exponent => 8,
+            Precedence::Prefix => 9,
+            Precedence::Call => 10,
         }
     }
 }
@@ -0,0 +1,190 @@
+use std::rc::Rc;
+
+use num_traits::{Signed, ToPrimitive};
+
+use crate::{
+    expression::Expression,
+    lexer::Lexer,
+    object::Object,
+    token::Token,
+    visitor::{
+        walk_call_expression, walk_infix_expression, walk_lambda, walk_let, walk_member_expression,
+        walk_prefix_expression, Visitor,
+    },
+};
+
+#[derive(Debug)]
+pub enum JsonError {
+    Malformed(serde_json::Error),
+    UnExpectedOperator { operator: Token },
+    InvalidIdentifier(String),
+    ExpectedFunctionName { found: Expression },
+    // Literals are never negative in a parsed tree, the sign is a prefix '-'.
+    NegativeLiteral { found: Expression },
+    // The lexer reads integers that fit in an i64 as `Int`.
+    InvalidBigInt { found: Expression },
+    UnExpectedNil,
+}
+
+pub fn expression_to_json(expression: &Expression) -> Result<String, JsonError> {
+    serde_json::to_string(expression).map_err(JsonError::Malformed)
+}
+
+/// Reads an expression written by [`expression_to_json`], rejecting trees the
+/// parser could not have produced, so that the result can be printed back to
+/// source and parsed to the same expression.
+pub fn expression_from_json(json: &str) -> Result<Expression, JsonError> {
+    let expression: Expression = serde_json::from_str(json).map_err(JsonError::Malformed)?;
+    let mut validator = Validator { error: None };
+    validator.visit_expression(&expression);
+    match validator.error {
+        Some(error) => Err(error),
+        None => Ok(expression),
+    }
+}
+
+pub fn object_to_json(object: &Object) -> Result<String, JsonError> {
+    serde_json::to_string(object).map_err(JsonError::Malformed)
+}
+
+pub fn object_from_json(json: &str) -> Result<Object, JsonError> {
+    serde_json::from_str(json).map_err(JsonError::Malformed)
+}
+
+/// JSON has no numbers for NaN and the infinities, so doubles that are not
+/// finite are written as the strings `"NaN"`, `"Infinity"` and `"-Infinity"`.
+pub(crate) mod double {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Double {
+        Finite(f64),
+        NonFinite(String),
+    }
+
+    pub(crate) fn serialize<S: Serializer>(double: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        match *double {
+            double if double.is_finite() => serializer.serialize_f64(double),
+            double if double.is_nan() => serializer.serialize_str("NaN"),
+            f64::INFINITY => serializer.serialize_str("Infinity"),
+            _ => serializer.serialize_str("-Infinity"),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        match Double::deserialize(deserializer)? {
+            Double::Finite(double) => Ok(double),
+            Double::NonFinite(text) => match text.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "Infinity" => Ok(f64::INFINITY),
+                "-Infinity" => Ok(f64::NEG_INFINITY),
+                _ => Err(D::Error::custom(format!(
+                    "expected a double, found {text:?}"
+                ))),
+            },
+        }
+    }
+}
+
+/// Remembers the first reason the visited tree is not one the parser builds.
+struct Validator {
+    error: Option<JsonError>,
+}
+
+impl Validator {
+    fn check(&mut self, is_valid: bool, error: impl FnOnce() -> JsonError) {
+        if !is_valid && self.error.is_none() {
+            self.error = Some(error());
+        }
+    }
+
+    fn check_identifier(&mut self, ident: &Rc<String>) {
+        self.check(Lexer::is_identifier(ident), || {
+            JsonError::InvalidIdentifier(ident.to_string())
+        });
+    }
+}
+
+impl Visitor for Validator {
+    fn visit_prefix_expression(&mut self, operator: &Token, expression: &Expression) {
+        self.check(operator.is_prefix_operator(), || {
+            JsonError::UnExpectedOperator {
+                operator: operator.clone(),
+            }
+        });
+        walk_prefix_expression(self, operator, expression);
+    }
+
+    fn visit_infix_expression(&mut self, operator: &Token, left: &Expression, right: &Expression) {
+        self.check(operator.is_infix_operator(), || {
+            JsonError::UnExpectedOperator {
+                operator: operator.clone(),
+            }
+        });
+        walk_infix_expression(self, operator, left, right);
+    }
+
+    fn visit_call_expression(&mut self, function: &Expression, arguments: &[Box<Expression>]) {
+        let is_callable = matches!(
+            function,
+            Expression::Ident(_) | Expression::MemberExpression { .. }
+        );
+        self.check(is_callable, || JsonError::ExpectedFunctionName {
+            found: function.clone(),
+        });
+        walk_call_expression(self, function, arguments);
+    }
+
+    fn visit_lambda(&mut self, parameters: &[Rc<String>], body: &Expression) {
+        for parameter in parameters {
+            self.check_identifier(parameter);
+        }
+        walk_lambda(self, parameters, body);
+    }
+
+    fn visit_let(&mut self, name: &Rc<String>, value: &Expression, body: &Expression) {
+        self.check_identifier(name);
+        walk_let(self, name, value, body);
+    }
+
+    fn visit_member_expression(
+        &mut self,
+        expression: &Expression,
+        member: &Rc<String>,
+        null_safe: bool,
+    ) {
+        self.check_identifier(member);
+        walk_member_expression(self, expression, member, null_safe);
+    }
+
+    fn visit_ident(&mut self, ident: &Rc<String>) {
+        self.check_identifier(ident);
+    }
+
+    fn visit_bare_ident(&mut self, ident: &Rc<String>) {
+        self.check_identifier(ident);
+    }
+
+    fn visit_function_name(&mut self, name: &Rc<String>, _arity: usize) {
+        self.check_identifier(name);
+    }
+
+    fn visit_literal(&mut self, literal: &Expression) {
+        let is_negative = match literal {
+            Expression::Int(int) => *int < 0,
+            Expression::Double(double) => double.is_sign_negative(),
+            Expression::Decimal(decimal) => decimal.is_sign_negative(),
+            Expression::BigInt(int) => int.is_negative(),
+            _ => false,
+        };
+        self.check(!is_negative, || JsonError::NegativeLiteral {
+            found: literal.clone(),
+        });
+        let is_small_big_int = matches!(literal, Expression::BigInt(int) if int.to_i64().is_some());
+        self.check(!is_small_big_int, || JsonError::InvalidBigInt {
+            found: literal.clone(),
+        });
+        self.check(*literal != Expression::Nil, || JsonError::UnExpectedNil);
+    }
+}
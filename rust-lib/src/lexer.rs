@@ -2,15 +2,24 @@
 
 use std::rc::Rc;
 
-use crate::token::Token;
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
 
-#[derive(Debug)]
+use crate::{
+    expression::Span,
+    token::{TemplateToken, Token},
+};
+
+#[derive(Debug, Clone)]
 pub(crate) struct Lexer {
     source: Vec<u8>,
     read_position: Option<usize>,
     last_position: Option<usize>,
     peek_position: Option<usize>,
     eof_returned: bool,
+    last_span: Span, // span of the token most recently returned by `next`
+    decimal_literals: bool,
+    caret_power: bool,
 }
 
 impl Lexer {
@@ -28,14 +37,79 @@ impl Lexer {
             0 => None,
             _ => Some(0),
         };
-        let to_return = Self {
+        Self {
             source,
             read_position,
             last_position,
             peek_position,
             eof_returned: false,
-        };
-        to_return
+            last_span: Span::default(),
+            decimal_literals: false,
+            caret_power: false,
+        }
+    }
+
+    /// Reads numbers with a fractional part as decimals even without the `m`
+    /// suffix, see `EvalOptions::decimal_as_default`.
+    pub(crate) fn with_decimal_literals(mut self, decimal_literals: bool) -> Self {
+        self.decimal_literals = decimal_literals;
+        self
+    }
+
+    pub(crate) fn decimal_literals(&self) -> bool {
+        self.decimal_literals
+    }
+
+    /// Reads `^` as `**`, see `EvalOptions::caret_power`.
+    pub(crate) fn with_caret_power(mut self, caret_power: bool) -> Self {
+        self.caret_power = caret_power;
+        self
+    }
+
+    pub(crate) fn caret_power(&self) -> bool {
+        self.caret_power
+    }
+
+    pub(crate) fn last_span(&self) -> Span {
+        self.last_span
+    }
+
+    fn keyword_token(identifier: &str) -> Option<Token> {
+        match identifier {
+            "not" => Some(Token::Not),
+            "and" => Some(Token::And),
+            "or" => Some(Token::Or),
+            "true" => Some(Token::True),
+            "false" => Some(Token::False),
+            "null" => Some(Token::Null),
+            "in" => Some(Token::In),
+            "let" => Some(Token::Let),
+            _ => None,
+        }
+    }
+
+    /// Whether `name` would be read back as a single `Token::Ident`.
+    pub(crate) fn is_identifier(name: &str) -> bool {
+        let mut bytes = name.bytes();
+        matches!(bytes.next(), Some(b'a'..=b'z' | b'A'..=b'Z'))
+            && bytes.all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+            && Lexer::keyword_token(name).is_none()
+    }
+
+    /// Called with `not` just read, reads a following `in` as part of the same
+    /// token. Nothing is consumed when the next word is not `in`.
+    fn read_in_after_not(&mut self) -> bool {
+        let (read_position, peek_position) = (self.read_position, self.peek_position);
+        self.read_char();
+        self.skip_whitespaces();
+        if let Some(start) = self.read_position {
+            if self.source[start].is_ascii_alphabetic() && self.read_identifier(start) == "in" {
+                return true;
+            }
+        }
+        self.read_position = read_position;
+        self.peek_position = peek_position;
+        false
     }
 
     pub(crate) fn peek_char(&mut self) -> Option<u8> {
@@ -50,11 +124,12 @@ impl Lexer {
             return;
         };
         std::mem::swap(&mut self.read_position, &mut self.peek_position);
-        // this unwrap should be safe.
-        if peek_position >= self.last_position.unwrap() {
-            self.peek_position = None;
-        } else {
-            self.peek_position = Some(peek_position + 1)
+        // peek_position is only ever set when there is a last_position.
+        match self.last_position {
+            Some(last_position) if peek_position < last_position => {
+                self.peek_position = Some(peek_position + 1)
+            }
+            _ => self.peek_position = None,
         }
     }
 
@@ -71,64 +146,60 @@ impl Lexer {
     }
 
     fn get_integer_token(slice: &[u8]) -> Token {
-        return Token::IntLiteral(
-            i64::from_str_radix(
-                String::from_utf8(slice.iter().map(|x| *x).collect())
-                    .unwrap()
-                    .as_str(),
-                10,
-            )
-            .unwrap(),
-        );
+        match std::str::from_utf8(slice).map(|digits| digits.parse::<i64>()) {
+            Ok(Ok(int)) => Token::IntLiteral(int),
+            // Literals beyond i64 are read as big integers.
+            _ => match BigInt::parse_bytes(slice, 10) {
+                Some(int) => Token::BigIntLiteral(Rc::new(int)),
+                None => Token::Illegal,
+            },
+        }
     }
     fn get_double_token(slice: &[u8]) -> Token {
-        Token::DoubleLiteral(
-            String::from_utf8(slice.iter().map(|x| *x).collect())
-                .unwrap()
-                .parse::<f64>()
-                .unwrap(),
-        )
+        match std::str::from_utf8(slice).map(|digits| digits.parse::<f64>()) {
+            Ok(Ok(double)) => Token::DoubleLiteral(double),
+            _ => Token::Illegal,
+        }
+    }
+
+    fn get_decimal_token(slice: &[u8]) -> Token {
+        match std::str::from_utf8(slice).map(Decimal::from_str_exact) {
+            Ok(Ok(decimal)) => Token::DecimalLiteral(decimal),
+            _ => Token::Illegal,
+        }
     }
 
+    /// Reads an int, a double, or a decimal when the number ends with an `m`
+    /// as in C#, e.g. `0.1m`.
     fn read_number(&mut self, read_position: usize) -> Token {
         let mut has_fractional = false;
-        loop {
-            // TODO: remove unwraps.
+        let end = loop {
             match self.peek_position {
-                None => match has_fractional {
-                    false => return Lexer::get_integer_token(&self.source[read_position..]),
-                    true => return Lexer::get_double_token(&self.source[read_position..]),
-                },
+                None => break self.source.len(),
                 Some(peek_position) => match self.source[peek_position] {
-                    b'.' => {
-                        has_fractional = true;
-                        self.read_char();
-                        continue;
-                    }
-                    b'0'..=b'9' => {
-                        self.read_char();
-                        continue;
-                    }
-                    _ => match has_fractional {
-                        false => {
-                            return Lexer::get_integer_token(
-                                &self.source[read_position..peek_position],
-                            )
-                        }
-                        true => {
-                            return Lexer::get_double_token(
-                                &self.source[read_position..peek_position],
-                            )
-                        }
-                    },
+                    b'.' => has_fractional = true,
+                    b'0'..=b'9' => (),
+                    _ => break peek_position,
                 },
             }
+            self.read_char();
+        };
+        let has_suffix = matches!(self.source.get(end), Some(b'm' | b'M'));
+        if has_suffix {
+            self.read_char();
+        }
+        let digits = &self.source[read_position..end];
+        match has_fractional {
+            _ if has_suffix => Lexer::get_decimal_token(digits),
+            true if self.decimal_literals => Lexer::get_decimal_token(digits),
+            true => Lexer::get_double_token(digits),
+            false => Lexer::get_integer_token(digits),
         }
     }
 
     fn read_string(&mut self) -> Token {
         let mut chars: Vec<u8> = vec![];
-        let token = loop {
+        loop {
             self.read_char();
             let Some(read_position) = self.read_position else {
                         return Token::Illegal
@@ -136,7 +207,10 @@ impl Lexer {
 
             let ch = self.source[read_position];
             if ch == b'"' {
-                break Token::StringLiteral(Rc::new(String::from_utf8(chars).unwrap()));
+                break match String::from_utf8(chars) {
+                    Ok(string) => Token::StringLiteral(Rc::new(string)),
+                    Err(_) => Token::Illegal,
+                };
             }
 
             if ch == b'\\' {
@@ -144,31 +218,123 @@ impl Lexer {
                             break Token::Illegal
                 };
                 let next_char = self.source[peek_position];
-                let next_char = match next_char {
-                    b'"' => b'"',
-                    b'n' => b'\n',
-                    b't' => b'\x09',
-                    b'r' => b'\x0d',
-                    b'\\' => b'\x5c',
-                    _ => todo!("expected valid escape sequence, but found {}", next_char),
+                let Some(next_char) = Lexer::unescape(next_char) else {
+                    break Token::Illegal;
                 };
                 chars.push(next_char);
                 self.read_char();
                 continue;
             }
             chars.push(ch);
+        }
+    }
+
+    /// The character that `\\` followed by `ch` stands for in a string.
+    fn unescape(ch: u8) -> Option<u8> {
+        match ch {
+            b'"' => Some(b'"'),
+            b'n' => Some(b'\n'),
+            b't' => Some(b'\x09'),
+            b'r' => Some(b'\x0d'),
+            b'\\' => Some(b'\x5c'),
+            _ => None,
+        }
+    }
+
+    /// Called with the `"` of a `$"..."` template just read. The text is
+    /// unescaped like a string, with `{{` and `}}` standing for braces, while
+    /// the source of each `{expression}` or `{expression:format}` hole is kept
+    /// for the parser.
+    fn read_template(&mut self) -> Token {
+        let mut parts = vec![];
+        let mut text: Vec<u8> = vec![];
+        loop {
+            self.read_char();
+            let Some(read_position) = self.read_position else {
+                return Token::Illegal;
+            };
+            let ch = self.source[read_position];
+            let next_char = self.peek_position.map(|position| self.source[position]);
+            match (ch, next_char) {
+                (b'"', _) => break,
+                (b'{', Some(b'{')) | (b'}', Some(b'}')) => {
+                    text.push(ch);
+                    self.read_char();
+                }
+                (b'{', _) => {
+                    if !text.is_empty() {
+                        let Ok(string) = String::from_utf8(std::mem::take(&mut text)) else {
+                            return Token::Illegal;
+                        };
+                        parts.push(TemplateToken::Text(Rc::new(string)));
+                    }
+                    let Some(hole) = self.read_template_hole() else {
+                        return Token::Illegal;
+                    };
+                    parts.push(hole);
+                }
+                (b'}', _) => return Token::Illegal,
+                (b'\\', Some(next_char)) => {
+                    let Some(next_char) = Lexer::unescape(next_char) else {
+                        return Token::Illegal;
+                    };
+                    text.push(next_char);
+                    self.read_char();
+                }
+                _ => text.push(ch),
+            }
+        }
+        if !text.is_empty() {
+            let Ok(string) = String::from_utf8(text) else {
+                return Token::Illegal;
+            };
+            parts.push(TemplateToken::Text(Rc::new(string)));
+        }
+        Token::Template(Rc::new(parts))
+    }
+
+    /// Called with the `{` of a template hole just read, reads up to the
+    /// matching `}`. A `:` outside of any brackets or string starts the format.
+    fn read_template_hole(&mut self) -> Option<TemplateToken> {
+        let start = self.read_position? + 1;
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut colon = None;
+        loop {
+            self.read_char();
+            let position = self.read_position?;
+            match self.source[position] {
+                b'\\' if in_string => self.read_char(),
+                b'"' => in_string = !in_string,
+                _ if in_string => (),
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' => depth -= 1,
+                b'}' if depth > 0 => depth -= 1,
+                b'}' => break,
+                b':' if depth == 0 && colon.is_none() => colon = Some(position),
+                _ => (),
+            }
+        }
+        let end = self.read_position?;
+        let text = |range: std::ops::Range<usize>| {
+            String::from_utf8(self.source[range].to_vec()).ok().map(Rc::new)
+        };
+        let format = match colon {
+            Some(colon) => Some(text(colon + 1..end)?),
+            None => None,
         };
-        token
+        Some(TemplateToken::Hole {
+            source: text(start..colon.unwrap_or(end))?,
+            offset: start,
+            format,
+        })
     }
 
     fn read_identifier(&mut self, read_position: usize) -> String {
         loop {
             match self.peek_position {
                 None => {
-                    return String::from_utf8(
-                        self.source[read_position..].iter().map(|x| *x).collect(),
-                    )
-                    .unwrap();
+                    return String::from_utf8_lossy(&self.source[read_position..]).into_owned();
                 }
                 Some(peek_position) => match self.source[peek_position] {
                     b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'0'..=b'9' => {
@@ -176,13 +342,8 @@ impl Lexer {
                         continue;
                     }
                     _ => {
-                        return String::from_utf8(
-                            self.source[read_position..peek_position]
-                                .iter()
-                                .map(|x| *x)
-                                .collect(),
-                        )
-                        .unwrap()
+                        return String::from_utf8_lossy(&self.source[read_position..peek_position])
+                            .into_owned()
                     }
                 },
             }
@@ -196,6 +357,10 @@ impl Iterator for Lexer {
     fn next(&mut self) -> Option<Self::Item> {
         self.skip_whitespaces();
         let Some(read_position) = self.read_position else {
+            self.last_span = Span {
+                start: self.source.len(),
+                end: self.source.len(),
+            };
             match self.eof_returned {
                 false => {
                     self.eof_returned = true;
@@ -212,22 +377,40 @@ impl Iterator for Lexer {
             b')' => Token::RParen,
             b'[' => Token::LBracket,
             b']' => Token::RBracket,
+            b'{' => Token::LBrace,
+            b'}' => Token::RBrace,
             b'+' => Token::Plus,
             b'-' => Token::Minus,
-            b'*' => Token::Asterisk,
-            b'/' => Token::Slash,
+            b'*' => match self.peek_position {
+                Some(peek_position) if self.source[peek_position] == b'*' => {
+                    self.read_char();
+                    Token::DoubleAsterisk
+                }
+                _ => Token::Asterisk,
+            },
+            // The spelling of `**` in legacy formulas.
+            b'^' if self.caret_power => Token::DoubleAsterisk,
+            b'/' => match self.peek_position {
+                Some(peek_position) if self.source[peek_position] == b'/' => {
+                    self.read_char();
+                    Token::DoubleSlash
+                }
+                _ => Token::Slash,
+            },
             b'%' => Token::Percent,
             b'=' => match self.peek_position {
-                // There is no assign operator i.e '=' in expressions.
-                None => Token::Illegal,
-                Some(peek_position) => {
-                    if b'=' == self.source[peek_position] {
+                None => Token::Assign,
+                Some(peek_position) => match self.source[peek_position] {
+                    b'=' => {
                         self.read_char();
                         Token::Equals
-                    } else {
-                        Token::Illegal
                     }
-                }
+                    b'>' => {
+                        self.read_char();
+                        Token::Arrow
+                    }
+                    _ => Token::Assign,
+                },
             },
             b'!' => match self.peek_position {
                 None => Token::Bang,
@@ -284,23 +467,49 @@ impl Iterator for Lexer {
                     _ => Token::Illegal,
                 },
             },
+            b'?' => match self.peek_position {
+                None => Token::Illegal,
+                Some(peek_position) => match self.source[peek_position] {
+                    b'?' => {
+                        self.read_char();
+                        Token::DoubleQuestionMark
+                    }
+                    b'.' => {
+                        self.read_char();
+                        Token::QuestionDot
+                    }
+                    _ => Token::Illegal,
+                },
+            },
             b',' => Token::Comma,
+            b'.' => Token::Dot,
+            b';' => Token::Semicolon,
             b'a'..=b'z' | b'A'..=b'Z' => {
                 let identifier = self.read_identifier(read_position);
-                match identifier.as_str() {
-                    "not" => Token::Not,
-                    "and" => Token::And,
-                    "or" => Token::Or,
-                    "true" => Token::True,
-                    "false" => Token::False,
-                    _ => Token::Ident(Rc::new(identifier)),
+                match Lexer::keyword_token(&identifier) {
+                    Some(Token::Not) if self.read_in_after_not() => Token::NotIn,
+                    Some(keyword) => keyword,
+                    None => Token::Ident(Rc::new(identifier)),
                 }
             }
             b'0'..=b'9' => self.read_number(read_position),
             b'"' => self.read_string(),
+            b'$' => match self.peek_position {
+                Some(peek_position) if self.source[peek_position] == b'"' => {
+                    self.read_char();
+                    self.read_template()
+                }
+                _ => Token::Illegal,
+            },
             // TODO: handle datetimes
             _ => Token::Illegal,
         };
+        self.last_span = Span {
+            start: read_position,
+            end: self
+                .read_position
+                .map_or(self.source.len(), |position| position + 1),
+        };
         self.read_char();
         Some(token)
     }
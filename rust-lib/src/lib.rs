@@ -4,13 +4,22 @@
 
 use object::{Context, Object};
 
+pub mod analysis;
+pub mod binary;
 pub mod evaluator;
-pub(crate) mod expression;
+pub mod expression;
+#[cfg(feature = "json")]
+pub mod json;
 pub(crate) mod lexer;
 pub mod object;
-pub(crate) mod parser;
+pub mod optimizer;
+pub mod parser;
+pub(crate) mod printer;
+pub mod rewrite;
 pub(crate) mod tests;
-pub(crate) mod token;
+pub mod token;
+pub mod typeck;
+pub mod visitor;
 
 // TODO: once the integration with 'c' is done, make this &str,
 pub struct MathematicalExpression {
@@ -1,26 +1,113 @@
 use std::{collections::HashMap, rc::Rc};
 
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
+/// What evaluating a parameter that is missing from the [`Context`] gives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingParameterPolicy {
+    /// Fail with `EvalError::ReferenceNotFound`.
+    #[default]
+    Error,
+    /// Evaluate to [`Object::Null`], for inputs that are optional.
+    Null,
+}
+
+/// What `/`, `//` or `%` by zero gives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DivisionByZeroPolicy {
+    /// Follow IEEE 754 for doubles: `1 / 0` is infinity and `0 / 0` is NaN.
+    /// Integer `//` and `%` by zero, which have no such result, still fail.
+    #[default]
+    Ieee,
+    /// Fail with `EvalError::DivideByZero`.
+    Error,
+    /// Evaluate to [`Object::Null`].
+    Null,
+}
+
+/// Settings that change how an expression is evaluated.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EvalOptions {
+    pub missing_parameter: MissingParameterPolicy,
+    pub division_by_zero: DivisionByZeroPolicy,
+    /// Fail with `EvalError::NonFiniteResult` when an operator or function
+    /// gives NaN or an infinity.
+    pub reject_non_finite: bool,
+    /// Read number literals with a fractional part, such as `0.1`, as
+    /// decimals rather than doubles, like NCalc's `DecimalAsDefault`. Applies
+    /// to input parsed with the options, as by `eval_input`,
+    /// `eval_script_input` and `parser::parse_input_with`.
+    pub decimal_as_default: bool,
+    /// Read `^` as the `**` exponent operator, as legacy formulas write it.
+    /// Applies to input parsed with the options, like `decimal_as_default`.
+    pub caret_power: bool,
+    /// Keep arithmetic on integers exact: `+`, `-`, `*`, `//`, `%`, `**` and
+    /// negation of ints give an int, or an [`Object::BigInt`] when the result
+    /// does not fit in an i64, rather than falling back to a double.
+    pub promote_to_big_int: bool,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Context {
     _inner: Rc<HashMap<String, Object>>,
+    options: EvalOptions,
+    parent: Option<Rc<Context>>,
 }
 
 impl Context {
-    pub(crate) fn get(&self, key: &str) -> Option<Object> {
-        self._inner.get(key).map(|obj| obj.clone())
+    pub fn get(&self, key: &str) -> Option<Object> {
+        match self._inner.get(key) {
+            Some(object) => Some(object.clone()),
+            None => self.parent.as_ref().and_then(|parent| parent.get(key)),
+        }
+    }
+
+    /// Sets `key` in this context, hiding any value it has in a parent.
+    pub fn set(&mut self, key: &str, value: Object) {
+        Rc::make_mut(&mut self._inner).insert(key.to_string(), value);
     }
 
-    pub(crate) fn from_map(map: HashMap<String, Object>) -> Self {
+    pub fn from_map(map: HashMap<String, Object>) -> Self {
         Self {
             _inner: Rc::new(map),
+            options: EvalOptions::default(),
+            parent: None,
+        }
+    }
+
+    /// A context in which `bindings` hide the values of the same name in this
+    /// one, e.g. to bind the parameters of a lambda. Options are inherited.
+    pub fn child(&self, bindings: HashMap<String, Object>) -> Self {
+        Self {
+            _inner: Rc::new(bindings),
+            options: self.options,
+            parent: Some(Rc::new(self.clone())),
         }
     }
+
+    pub fn with_options(mut self, options: EvalOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> EvalOptions {
+        self.options
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub enum Object {
     Int(i64),
-    Double(f64),
+    Double(#[cfg_attr(feature = "json", serde(with = "crate::json::double"))] f64),
+    Decimal(Decimal),
+    /// An integer beyond the range of i64. Arithmetic gives an `Int` for any
+    /// result that fits.
+    BigInt(Rc<BigInt>),
     Bool(bool),
     Str(Rc<String>),
+    Null,
+    List(Rc<Vec<Object>>),
+    Map(Rc<HashMap<String, Object>>),
 }
@@ -0,0 +1,436 @@
+use std::{collections::HashMap, rc::Rc};
+
+use num_bigint::BigInt;
+use num_traits::Signed;
+
+use crate::{
+    evaluator,
+    expression::{Expression, TemplatePart},
+    object::{Context, DivisionByZeroPolicy, EvalOptions, Object},
+    token::Token,
+};
+
+/// Returns a simplified copy of `expression`.
+///
+/// Constant subexpressions are folded by running them through the evaluator,
+/// so the result is whatever `eval` would have produced. A subexpression is
+/// left untouched when evaluating it fails or produces a value that has no
+/// literal form (e.g. `1 / 0`), so the error still surfaces at runtime.
+///
+/// `true and x`, `x and true`, `false or x` and `x or false` are rewritten to
+/// `x` when `x` can only give a bool or null, e.g. a comparison. Arithmetic
+/// identities such as `x * 1` are kept, since without knowing the type of `x`
+/// they could change the result (`[x] * 1` is `2.0` for `[x] = 2`) or hide
+/// an error.
+pub fn optimize(expression: &Expression) -> Expression {
+    Folder {
+        context: Context::default(),
+        simplify_identities: true,
+        bound: vec![],
+    }
+    .fold(expression)
+}
+
+/// Evaluates as much of `expression` as `context` allows and returns what is
+/// left to evaluate once the missing parameters are known.
+///
+/// Parameters found in `context` are replaced by their values, constant
+/// subexpressions are folded like in [`optimize`], and `if` and `and`/`or`
+/// only keep the branches the evaluator could still take. Evaluating the
+/// result with the remaining parameters gives the same value as evaluating
+/// `expression` with all of them. For that, `expression` should be parsed with
+/// the options of `context`, see [`crate::parser::parse_input_with`].
+pub fn partial_eval(expression: &Expression, context: Context) -> Expression {
+    Folder {
+        context,
+        simplify_identities: false,
+        bound: vec![],
+    }
+    .fold(expression)
+}
+
+struct Folder {
+    context: Context,
+    simplify_identities: bool,
+    // Parameters of the enclosing lambdas and names of the enclosing lets, which
+    // hide the context.
+    bound: Vec<Rc<String>>,
+}
+
+impl Folder {
+    fn fold(&self, expression: &Expression) -> Expression {
+        match expression {
+            Expression::Ident(ident) | Expression::BareIdent(ident)
+                if self.bound.contains(ident) =>
+            {
+                expression.clone()
+            }
+            Expression::Ident(ident) | Expression::BareIdent(ident) => self
+                .context
+                .get(ident)
+                .and_then(|object| object_to_expression(&object))
+                .unwrap_or_else(|| expression.clone()),
+            Expression::Lambda { parameters, body } => {
+                let folder = Folder {
+                    context: self.context.clone(),
+                    simplify_identities: self.simplify_identities,
+                    bound: self.bound.iter().chain(parameters).cloned().collect(),
+                };
+                Expression::Lambda {
+                    parameters: parameters.clone(),
+                    body: Box::new(folder.fold(body)),
+                }
+            }
+            Expression::Let { name, value, body } => {
+                let value = self.fold(value);
+                let bound: Vec<Rc<String>> = self
+                    .bound
+                    .iter()
+                    .filter(|bound| *bound != name)
+                    .cloned()
+                    .collect();
+                // A constant value is substituted into the body, dropping the let.
+                let constant = match is_constant(&value) {
+                    true => evaluator::eval(&value, Context::default()).ok(),
+                    false => None,
+                };
+                if let Some(constant) = constant {
+                    let folder = Folder {
+                        context: self
+                            .context
+                            .child(HashMap::from_iter([(name.to_string(), constant)])),
+                        simplify_identities: self.simplify_identities,
+                        bound,
+                    };
+                    return folder.fold(body);
+                }
+                let folder = Folder {
+                    context: self.context.clone(),
+                    simplify_identities: self.simplify_identities,
+                    bound: bound.into_iter().chain([name.clone()]).collect(),
+                };
+                Expression::Let {
+                    name: name.clone(),
+                    value: Box::new(value),
+                    body: Box::new(folder.fold(body)),
+                }
+            }
+            Expression::PrefixExpression {
+                operator,
+                expression,
+            } => {
+                let inner = self.fold(expression);
+                let is_foldable = is_constant(&inner);
+                let expression = Expression::PrefixExpression {
+                    operator: operator.clone(),
+                    expression: Box::new(inner),
+                };
+                match is_foldable {
+                    true => fold_constant(expression, self.context.options()),
+                    false => expression,
+                }
+            }
+            Expression::InfixExpression {
+                operator,
+                left,
+                right,
+            } => self.fold_infix_expression(operator, left, right),
+            Expression::CallExpression {
+                function,
+                arguments,
+            } => self.fold_fn_call(function, arguments),
+            Expression::List(elements) => Expression::List(
+                elements
+                    .iter()
+                    .map(|element| Box::new(self.fold(element)))
+                    .collect(),
+            ),
+            Expression::Template(parts) => {
+                let parts: Vec<TemplatePart> = parts
+                    .iter()
+                    .map(|part| match part {
+                        TemplatePart::Value { expression, format } => TemplatePart::Value {
+                            expression: Box::new(self.fold(expression)),
+                            format: format.clone(),
+                        },
+                        text => text.clone(),
+                    })
+                    .collect();
+                let is_foldable = parts.iter().all(|part| match part {
+                    TemplatePart::Value { expression, .. } => is_constant(expression),
+                    TemplatePart::Text(_) => true,
+                });
+                let template = Expression::Template(parts);
+                match is_foldable {
+                    true => fold_constant(template, self.context.options()),
+                    false => template,
+                }
+            }
+            Expression::IndexExpression { expression, index } => {
+                let expression = self.fold(expression);
+                let index = self.fold(index);
+                let is_foldable = is_constant(&expression) && is_constant(&index);
+                let expression = Expression::IndexExpression {
+                    expression: Box::new(expression),
+                    index: Box::new(index),
+                };
+                match is_foldable {
+                    true => fold_constant(expression, self.context.options()),
+                    false => expression,
+                }
+            }
+            Expression::MemberExpression {
+                expression: map,
+                member,
+                null_safe,
+            } => match member_root(map) {
+                // Maps have no literal form, so the members of a known parameter
+                // are looked up right away.
+                Some(root) if !self.bound.contains(root) && self.context.get(root).is_some() => {
+                    evaluator::eval(expression, self.context.clone())
+                        .ok()
+                        .and_then(|object| object_to_expression(&object))
+                        .unwrap_or_else(|| expression.clone())
+                }
+                _ => Expression::MemberExpression {
+                    expression: Box::new(self.fold(map)),
+                    member: member.clone(),
+                    null_safe: *null_safe,
+                },
+            },
+            _ => expression.clone(),
+        }
+    }
+
+    fn fold_infix_expression(
+        &self,
+        operator: &Token,
+        left: &Expression,
+        right: &Expression,
+    ) -> Expression {
+        let left = self.fold(left);
+        if *operator == Token::DoubleQuestionMark && is_constant(&left) {
+            // Like the evaluator, only look at the right operand when the left
+            // one is null.
+            return match left {
+                Expression::Null => self.fold(right),
+                left => left,
+            };
+        }
+        if let Some(short_circuit_on) = short_circuit_value(operator) {
+            // Mirrors the evaluator: the right operand is never looked at when the
+            // left one already decides the result.
+            match left {
+                Expression::Bool(left) if left == short_circuit_on => {
+                    return Expression::Bool(left);
+                }
+                _ => (),
+            }
+        }
+        let right = self.fold(right);
+
+        if is_constant(&left) && is_constant(&right) {
+            return fold_constant(
+                Expression::InfixExpression {
+                    operator: operator.clone(),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+                self.context.options(),
+            );
+        }
+
+        if self.simplify_identities {
+            match (operator, &left, &right) {
+                (Token::And | Token::DoubleAmpersand, Expression::Bool(true), operand)
+                | (Token::And | Token::DoubleAmpersand, operand, Expression::Bool(true))
+                | (Token::Or | Token::DoublePipe, Expression::Bool(false), operand)
+                | (Token::Or | Token::DoublePipe, operand, Expression::Bool(false))
+                    if is_boolean(operand) =>
+                {
+                    return operand.clone();
+                }
+                _ => (),
+            }
+        }
+        Expression::InfixExpression {
+            operator: operator.clone(),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    fn fold_fn_call(&self, function: &Expression, arguments: &[Box<Expression>]) -> Expression {
+        if let (Expression::Ident(function_name), [condition, when_true, when_false]) =
+            (function, arguments)
+        {
+            if function_name.as_str() == "if" {
+                // Only the branch picked by the condition would be evaluated.
+                let condition = self.fold(condition);
+                match condition {
+                    Expression::Bool(true) => return self.fold(when_true),
+                    // A null condition takes the false branch, as in the evaluator.
+                    Expression::Bool(false) | Expression::Null => return self.fold(when_false),
+                    _ => {
+                        return Expression::CallExpression {
+                            function: Box::new(function.clone()),
+                            arguments: vec![
+                                Box::new(condition),
+                                Box::new(self.fold(when_true)),
+                                Box::new(self.fold(when_false)),
+                            ],
+                        }
+                    }
+                }
+            }
+        }
+
+        let function = match function {
+            Expression::MemberExpression {
+                expression: receiver,
+                member,
+                null_safe,
+            } => Expression::MemberExpression {
+                expression: Box::new(self.fold(receiver)),
+                member: member.clone(),
+                null_safe: *null_safe,
+            },
+            function => function.clone(),
+        };
+        Expression::CallExpression {
+            function: Box::new(function),
+            arguments: arguments
+                .iter()
+                .map(|argument| Box::new(self.fold(argument)))
+                .collect(),
+        }
+    }
+}
+
+/// The parameter a chain of member accesses such as `[customer].address.city`
+/// starts from.
+fn member_root(expression: &Expression) -> Option<&Rc<String>> {
+    match expression {
+        Expression::Ident(ident) | Expression::BareIdent(ident) => Some(ident),
+        Expression::MemberExpression { expression, .. } => member_root(expression),
+        _ => None,
+    }
+}
+
+/// For `and`/`or`, the value of the left operand that makes evaluating the
+/// right one unnecessary.
+fn short_circuit_value(operator: &Token) -> Option<bool> {
+    match operator {
+        Token::And | Token::DoubleAmpersand => Some(false),
+        Token::Or | Token::DoublePipe => Some(true),
+        _ => None,
+    }
+}
+
+/// Whether `expression` gives a bool or null whenever evaluating it succeeds.
+fn is_boolean(expression: &Expression) -> bool {
+    match expression {
+        Expression::Bool(_) | Expression::Null => true,
+        Expression::PrefixExpression { operator, .. } => {
+            matches!(operator, Token::Bang | Token::Not)
+        }
+        Expression::InfixExpression { operator, .. } => matches!(
+            operator,
+            Token::LessThan
+                | Token::LessThanEqualTo
+                | Token::GreaterThan
+                | Token::GreaterThanEqualTo
+                | Token::Equals
+                | Token::NotEquals
+                | Token::NotEqualsAngleBrackets
+                | Token::And
+                | Token::DoubleAmpersand
+                | Token::Or
+                | Token::DoublePipe
+                | Token::In
+                | Token::NotIn
+        ),
+        _ => false,
+    }
+}
+
+fn is_constant(expression: &Expression) -> bool {
+    match expression {
+        Expression::Int(_)
+        | Expression::Double(_)
+        | Expression::Decimal(_)
+        | Expression::BigInt(_)
+        | Expression::Bool(_)
+        | Expression::Str(_)
+        | Expression::Null => true,
+        Expression::PrefixExpression {
+            operator: Token::Minus,
+            expression,
+        } => matches!(
+            expression.as_ref(),
+            Expression::Int(_)
+                | Expression::Double(_)
+                | Expression::Decimal(_)
+                | Expression::BigInt(_)
+        ),
+        Expression::List(elements) => elements.iter().all(|element| is_constant(element)),
+        _ => false,
+    }
+}
+
+/// Evaluates `expression`, whose operands are all constants, with the options
+/// it is meant to be evaluated with, and replaces it with the resulting literal
+/// when that is possible.
+fn fold_constant(expression: Expression, options: EvalOptions) -> Expression {
+    // Options such as `promote_to_big_int` change the value, so they are kept.
+    // A division by zero or a non-finite result is left to the evaluation
+    // instead, which may fail where folding would not.
+    let options = EvalOptions {
+        division_by_zero: DivisionByZeroPolicy::Error,
+        reject_non_finite: true,
+        ..options
+    };
+    match evaluator::eval(&expression, Context::default().with_options(options)) {
+        Ok(object) => object_to_expression(&object).unwrap_or(expression),
+        Err(_) => expression,
+    }
+}
+
+/// Converts `object` into the expression that the parser would produce for its
+/// literal form, or `None` when there is no such literal.
+pub(crate) fn object_to_expression(object: &Object) -> Option<Expression> {
+    let negate = |expression: Expression| Expression::PrefixExpression {
+        operator: Token::Minus,
+        expression: Box::new(expression),
+    };
+    match object {
+        Object::Int(int) if *int >= 0 => Some(Expression::Int(*int)),
+        Object::Int(int) => Some(match int.checked_neg() {
+            Some(int) => negate(Expression::Int(int)),
+            // The literal for -i64::MIN is beyond i64.
+            None => negate(Expression::BigInt(Rc::new(-BigInt::from(*int)))),
+        }),
+        Object::Double(double) if !double.is_finite() => None,
+        Object::Double(double) if double.is_sign_negative() => {
+            Some(negate(Expression::Double(-double)))
+        }
+        Object::Double(double) => Some(Expression::Double(*double)),
+        Object::Decimal(decimal) if decimal.is_sign_negative() => {
+            Some(negate(Expression::Decimal(-decimal)))
+        }
+        Object::Decimal(decimal) => Some(Expression::Decimal(*decimal)),
+        Object::BigInt(int) if int.is_negative() => {
+            Some(negate(Expression::BigInt(Rc::new(-int.as_ref()))))
+        }
+        Object::BigInt(int) => Some(Expression::BigInt(int.clone())),
+        Object::Bool(bool) => Some(Expression::Bool(*bool)),
+        Object::Str(string) => Some(Expression::Str(string.clone())),
+        Object::Null => Some(Expression::Null),
+        Object::List(list) => list
+            .iter()
+            .map(|object| object_to_expression(object).map(Box::new))
+            .collect::<Option<Vec<_>>>()
+            .map(Expression::List),
+        Object::Map(_) => None,
+    }
+}
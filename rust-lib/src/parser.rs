@@ -1,13 +1,68 @@
+use std::rc::Rc;
+
 use crate::{
-    expression::{Expression, ParseExpressionError, Precedence},
+    expression::{
+        Assignment, Expression, ParseExpressionError, Precedence, Script, Span, SpanTree,
+        TemplatePart,
+    },
     lexer::Lexer,
-    token::Token,
+    object::EvalOptions,
+    token::{TemplateToken, Token},
 };
 
+/// Parses `input` into an [`Expression`] without evaluating it.
+pub fn parse_input(input: &str) -> Result<Expression, ParseExpressionError> {
+    parse_input_with(input, &EvalOptions::default())
+}
+
+/// Like [`parse_input`], but reads number literals and `^` the way `options`
+/// ask, see `EvalOptions::decimal_as_default` and `EvalOptions::caret_power`,
+/// so that the expression is the one `eval_input` would evaluate.
+pub fn parse_input_with(
+    input: &str,
+    options: &EvalOptions,
+) -> Result<Expression, ParseExpressionError> {
+    Parser::new(lexer(input, options))?.parse()
+}
+
+/// Like [`parse_input`], but also returns where each subexpression is in `input`.
+pub fn parse_input_with_spans(input: &str) -> Result<(Expression, SpanTree), ParseExpressionError> {
+    Parser::new(Lexer::from_input(input))?.parse_with_spans()
+}
+
+/// Parses `input` as a script: `;` separated assignments such as
+/// `[total] = [a] + [b]; [flag] = [total] > 100`. The last statement may be
+/// followed by a `;`.
+pub fn parse_script(input: &str) -> Result<Script, ParseExpressionError> {
+    parse_script_with(input, &EvalOptions::default())
+}
+
+/// Like [`parse_script`], reading the input the way `options` ask, see
+/// [`parse_input_with`].
+pub fn parse_script_with(
+    input: &str,
+    options: &EvalOptions,
+) -> Result<Script, ParseExpressionError> {
+    Parser::new(lexer(input, options))?.parse_script()
+}
+
+pub(crate) fn lexer(input: &str, options: &EvalOptions) -> Lexer {
+    Lexer::from_input(input)
+        .with_decimal_literals(options.decimal_as_default)
+        .with_caret_power(options.caret_power)
+}
+
 pub(crate) struct Parser {
     _lexer: Lexer,
     curr_token: Token,
     next_token: Token,
+    curr_span: Span,
+    next_span: Span,
+    // Spans of the expressions parsed so far that are not yet part of a bigger one.
+    spans: Vec<SpanTree>,
+    // Set while parsing the value of a `let`, where `in` ends the value rather
+    // than being an operator. Brackets of any kind clear it.
+    no_in: bool,
 }
 
 impl Parser {
@@ -17,12 +72,18 @@ impl Parser {
         }
         // This means at the very least, we will have one user defined token
         // and one EOF token. That means 2 times doing next should be fine.
-        let curr_token = lexer.next().unwrap();
-        let next_token = lexer.next().unwrap();
+        let curr_token = lexer.next().unwrap_or(Token::EOF);
+        let curr_span = lexer.last_span();
+        let next_token = lexer.next().unwrap_or(Token::EOF);
+        let next_span = lexer.last_span();
         Ok(Self {
             _lexer: lexer,
             curr_token,
             next_token,
+            curr_span,
+            next_span,
+            spans: vec![],
+            no_in: false,
         })
     }
 
@@ -34,12 +95,131 @@ impl Parser {
         self.parse_expression(Precedence::Lowest)
     }
 
+    pub(crate) fn parse_with_spans(
+        &mut self,
+    ) -> Result<(Expression, SpanTree), ParseExpressionError> {
+        let expression = self.parse()?;
+        Ok((expression, self.spans.pop().unwrap_or_default()))
+    }
+
+    pub(crate) fn parse_script(&mut self) -> Result<Script, ParseExpressionError> {
+        let mut statements = vec![];
+        while self.curr_token != Token::EOF {
+            statements.push(self.parse_assignment()?);
+            self.advance_token()?;
+            match self.curr_token {
+                Token::Semicolon => self.advance_token()?,
+                Token::EOF => (),
+                _ => {
+                    return Err(ParseExpressionError::ExpectedXFoundY {
+                        expected: "Token::Semicolon",
+                        found: self.curr_token.clone(),
+                    })
+                }
+            }
+        }
+        Ok(Script { statements })
+    }
+
+    /// Parses `[target] = value` or `target = value`, leaving the last token
+    /// of the value as the current token.
+    fn parse_assignment(&mut self) -> Result<Assignment, ParseExpressionError> {
+        let target = match self.parse_expression(Precedence::Call)? {
+            Expression::Ident(target) | Expression::BareIdent(target) => target,
+            _ => {
+                return Err(ParseExpressionError::ExpectedXFoundY {
+                    expected: "Identifier",
+                    found: self.curr_token.clone(),
+                })
+            }
+        };
+        self.advance_token()?;
+        if self.curr_token != Token::Assign {
+            return Err(ParseExpressionError::ExpectedXFoundY {
+                expected: "Token::Assign",
+                found: self.curr_token.clone(),
+            });
+        }
+        self.advance_token()?;
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.spans.clear();
+        Ok(Assignment { target, value })
+    }
+
+    fn push_leaf_span(&mut self, span: Span) {
+        self.spans.push(SpanTree {
+            span,
+            children: vec![],
+        });
+    }
+
+    fn last_span_start(&self) -> usize {
+        self.spans
+            .last()
+            .map_or(self.curr_span.start, |tree| tree.span.start)
+    }
+
+    /// Makes the spans of the last `count` parsed expressions the children of
+    /// the expression starting at `start` and ending at the current token.
+    fn reduce_spans(&mut self, count: usize, start: usize) {
+        let children = self.spans.split_off(self.spans.len().saturating_sub(count));
+        self.spans.push(SpanTree {
+            span: Span {
+                start,
+                end: self.curr_span.end,
+            },
+            children,
+        });
+    }
+
+    /// Runs `parse` with `no_in` set to the given value, restoring it after.
+    fn with_no_in<T>(&mut self, no_in: bool, parse: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = std::mem::replace(&mut self.no_in, no_in);
+        let result = parse(self);
+        self.no_in = outer;
+        result
+    }
+
+    /// Parses a parenthesized expression, or the parameters of a lambda such
+    /// as `(a, b) => a + b`.
     fn parse_grouped_expression(&mut self) -> Result<Expression, ParseExpressionError> {
+        self.with_no_in(false, Self::parse_grouped_expression_inner)
+    }
+
+    fn parse_grouped_expression_inner(&mut self) -> Result<Expression, ParseExpressionError> {
+        let start = self.curr_span.start;
+        if *self.peek_next() == Token::RParen {
+            self.advance_token()?;
+            return self.parse_lambda(vec![], start);
+        }
         self.advance_token()?;
         let expression = self.parse_expression(Precedence::Lowest)?;
         self.advance_token()?;
         match &self.curr_token {
-            Token::RParen => Ok(expression),
+            Token::RParen if *self.peek_next() == Token::Arrow => {
+                self.spans.pop();
+                let parameters = vec![lambda_parameter(expression)?];
+                self.parse_lambda(parameters, start)
+            }
+            Token::Comma => {
+                let mut parameters = vec![lambda_parameter(expression)?];
+                let rest = self.parse_expression_list(Token::RParen)?;
+                self.spans
+                    .truncate(self.spans.len().saturating_sub(rest.len() + 1));
+                for parameter in rest {
+                    parameters.push(lambda_parameter(*parameter)?);
+                }
+                self.parse_lambda(parameters, start)
+            }
+            Token::RParen => {
+                if let Some(tree) = self.spans.last_mut() {
+                    tree.span = Span {
+                        start,
+                        end: self.curr_span.end,
+                    };
+                }
+                Ok(expression)
+            }
             _ => Err(ParseExpressionError::ExpectedXFoundY {
                 expected: "Token::RParen",
                 found: self.curr_token.clone(),
@@ -47,20 +227,146 @@ impl Parser {
         }
     }
 
+    /// Parses `let name = value in body`. `let a = 1, b = a + 1 in body` is
+    /// read as `let a = 1 in let b = a + 1 in body`.
+    fn parse_let_expression(&mut self) -> Result<Expression, ParseExpressionError> {
+        let start = self.curr_span.start;
+        self.advance_token()?;
+        self.parse_let_binding(start)
+    }
+
+    /// Parses a `name = value` binding, starting at the current token, and
+    /// what follows it.
+    fn parse_let_binding(&mut self, start: usize) -> Result<Expression, ParseExpressionError> {
+        let Token::Ident(name) = self.curr_token.clone() else {
+            return Err(ParseExpressionError::ExpectedXFoundY {
+                expected: "Identifier",
+                found: self.curr_token.clone(),
+            });
+        };
+        self.advance_token()?;
+        if self.curr_token != Token::Assign {
+            return Err(ParseExpressionError::ExpectedXFoundY {
+                expected: "Token::Assign",
+                found: self.curr_token.clone(),
+            });
+        }
+        self.advance_token()?;
+        let value = self.with_no_in(true, |parser| parser.parse_expression(Precedence::Lowest))?;
+        self.advance_token()?;
+        let body = match &self.curr_token {
+            Token::Comma => {
+                self.advance_token()?;
+                let binding_start = self.curr_span.start;
+                self.parse_let_binding(binding_start)?
+            }
+            Token::In => {
+                self.advance_token()?;
+                self.parse_expression(Precedence::Lowest)?
+            }
+            _ => {
+                return Err(ParseExpressionError::ExpectedXFoundY {
+                    expected: "Token::In",
+                    found: self.curr_token.clone(),
+                })
+            }
+        };
+        self.reduce_spans(2, start);
+        Ok(Expression::Let {
+            name,
+            value: Box::new(value),
+            body: Box::new(body),
+        })
+    }
+
+    /// Parses the holes of the current `$"..."` token, each with a parser of its
+    /// own, and makes their spans the children of the template.
+    fn parse_template(
+        &mut self,
+        tokens: &[TemplateToken],
+    ) -> Result<Expression, ParseExpressionError> {
+        let mut parts = vec![];
+        let mut children = vec![];
+        for token in tokens {
+            let (source, offset, format) = match token {
+                TemplateToken::Text(text) => {
+                    parts.push(TemplatePart::Text(text.clone()));
+                    continue;
+                }
+                TemplateToken::Hole {
+                    source,
+                    offset,
+                    format,
+                } => (source, offset, format),
+            };
+            let lexer = Lexer::from_input(source)
+                .with_decimal_literals(self._lexer.decimal_literals())
+                .with_caret_power(self._lexer.caret_power());
+            let mut parser = Parser::new(lexer)?;
+            let (expression, mut spans) = parser.parse_with_spans()?;
+            if *parser.peek_next() != Token::EOF {
+                return Err(ParseExpressionError::UnexpectedToken {
+                    token: parser.next_token,
+                });
+            }
+            shift_spans(&mut spans, *offset);
+            children.push(spans);
+            parts.push(TemplatePart::Value {
+                expression: Box::new(expression),
+                format: format.clone(),
+            });
+        }
+        self.spans.push(SpanTree {
+            span: self.curr_span,
+            children,
+        });
+        Ok(Expression::Template(parts))
+    }
+
+    /// Parses the `=> body` of a lambda whose parameters end at the current
+    /// token.
+    fn parse_lambda(
+        &mut self,
+        parameters: Vec<Rc<String>>,
+        start: usize,
+    ) -> Result<Expression, ParseExpressionError> {
+        self.advance_token()?;
+        if self.curr_token != Token::Arrow {
+            return Err(ParseExpressionError::ExpectedXFoundY {
+                expected: "Token::Arrow",
+                found: self.curr_token.clone(),
+            });
+        }
+        self.advance_token()?;
+        let body = self.parse_expression(Precedence::Lowest)?;
+        self.reduce_spans(1, start);
+        Ok(Expression::Lambda {
+            parameters,
+            body: Box::new(body),
+        })
+    }
+
     fn advance_token(&mut self) -> Result<(), ParseExpressionError> {
         self.curr_token = self.next_token.clone();
+        self.curr_span = self.next_span;
         self.next_token = match self._lexer.next() {
             Some(token) => token,
             // None => return Err(ParseExpressionError::UnexpectedEnd),
             None => Token::EOF,
         };
+        self.next_span = self._lexer.last_span();
         Ok(())
     }
 
+    /// A prefix operator applies to the operand right after it, binding tighter
+    /// than any infix operator: `-2 * 3` is `(-2) * 3` and `not [a] or [b]` is
+    /// `(not [a]) or [b]`. Parenthesize to negate more, as in `not ([a] or [b])`.
     fn parse_prefix_expression(&mut self) -> Result<Expression, ParseExpressionError> {
         let prefix_operator = self.curr_token.clone();
-        let _ = self.advance_token()?;
-        let right_expression = self.parse_expression(prefix_operator.get_precedence())?;
+        let start = self.curr_span.start;
+        self.advance_token()?;
+        let right_expression = self.parse_expression(Precedence::Prefix)?;
+        self.reduce_spans(1, start);
         Ok(Expression::PrefixExpression {
             operator: prefix_operator,
             expression: Box::new(right_expression),
@@ -69,7 +375,8 @@ impl Parser {
 
     fn parse_bracket_ident_expression(&mut self) -> Result<Expression, ParseExpressionError> {
         let _l_bracket_token = self.curr_token.clone();
-        let _ = self.advance_token()?;
+        let start = self.curr_span.start;
+        self.advance_token()?;
         let x = match &self.curr_token {
             Token::Ident(ident) => Ok(Expression::Ident(ident.clone()))?,
             _ => {
@@ -80,6 +387,10 @@ impl Parser {
             }
         };
         self.advance_token()?;
+        self.push_leaf_span(Span {
+            start,
+            end: self.curr_span.end,
+        });
         Ok(x)
     }
 
@@ -88,9 +399,24 @@ impl Parser {
         left: Expression,
     ) -> Result<Expression, ParseExpressionError> {
         let operator = self.curr_token.clone();
+        let start = self.last_span_start();
         self.advance_token()?;
 
-        let right = self.parse_expression(operator.get_precedence())?;
+        let right = match operator {
+            // A parenthesized list of values rather than a grouped expression,
+            // unless the parentheses are doubled as in `[x] in (([a] ?? [b]))`.
+            Token::In | Token::NotIn if self.curr_token == Token::LParen => {
+                match self.is_doubly_parenthesized() {
+                    true => self.parse_doubly_parenthesized()?,
+                    false => self.parse_list(Token::RParen)?,
+                }
+            }
+            // Parsing the right operand one level lower lets it take in the
+            // next `**`, so that `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+            Token::DoubleAsterisk => self.parse_expression(Precedence::Product)?,
+            _ => self.parse_expression(operator.get_precedence())?,
+        };
+        self.reduce_spans(2, start);
         Ok(Expression::InfixExpression {
             operator,
             left: Box::new(left),
@@ -98,21 +424,171 @@ impl Parser {
         })
     }
 
-    fn parse_function_call_expression(
+    /// Whether the current `(` only holds a parenthesized expression, as in
+    /// `((expression))`.
+    fn is_doubly_parenthesized(&self) -> bool {
+        if *self.peek_next() != Token::LParen {
+            return false;
+        }
+        // The lexer is past the inner `(`.
+        let mut lexer = self._lexer.clone();
+        let mut depth = 1;
+        while depth > 0 {
+            match lexer.next() {
+                Some(Token::LParen) => depth += 1,
+                Some(Token::RParen) => depth -= 1,
+                Some(Token::EOF) | None => return false,
+                _ => (),
+            }
+        }
+        lexer.next() == Some(Token::RParen)
+    }
+
+    /// Parses `((expression))`, the outer `(` being the current token, as
+    /// `expression`.
+    fn parse_doubly_parenthesized(&mut self) -> Result<Expression, ParseExpressionError> {
+        let start = self.curr_span.start;
+        self.advance_token()?;
+        let expression = self.parse_grouped_expression()?;
+        self.advance_token()?;
+        if let Some(tree) = self.spans.last_mut() {
+            tree.span = Span {
+                start,
+                end: self.curr_span.end,
+            };
+        }
+        Ok(expression)
+    }
+
+    /// Parses the comma separated expressions between the current token and
+    /// the matching `end` token, which becomes the current token.
+    #[allow(clippy::vec_box)]
+    fn parse_expression_list(
         &mut self,
-        function: Expression,
-    ) -> Result<Expression, ParseExpressionError> {
-        self.advance_token()?; // curr_token is Token::LParen.
-        let mut args = vec![];
-        while self.curr_token != Token::RParen {
-            let arg = self.parse_expression(Precedence::Lowest)?;
-            args.push(Box::new(arg));
+        end: Token,
+    ) -> Result<Vec<Box<Expression>>, ParseExpressionError> {
+        self.with_no_in(false, |parser| parser.parse_expression_list_inner(end))
+    }
+
+    #[allow(clippy::vec_box)]
+    fn parse_expression_list_inner(
+        &mut self,
+        end: Token,
+    ) -> Result<Vec<Box<Expression>>, ParseExpressionError> {
+        let mut expressions = vec![];
+        self.advance_token()?;
+        while self.curr_token != end {
+            expressions.push(Box::new(self.parse_expression(Precedence::Lowest)?));
             self.advance_token()?;
-            if let Token::Comma = self.curr_token.clone() {
-                self.advance_token()?;
+            match &self.curr_token {
+                Token::Comma => self.advance_token()?,
+                token if *token == end => (),
+                _ => {
+                    return Err(ParseExpressionError::ExpectedXFoundY {
+                        expected: "Token::Comma",
+                        found: self.curr_token.clone(),
+                    })
+                }
             }
         }
+        Ok(expressions)
+    }
+
+    /// Parses a `{a, b, c}` list, or with `end` being `Token::RParen` the
+    /// `(a, b, c)` on the right of `in`/`not in`.
+    fn parse_list(&mut self, end: Token) -> Result<Expression, ParseExpressionError> {
+        let start = self.curr_span.start;
+        let elements = self.parse_expression_list(end)?;
+        self.reduce_spans(elements.len(), start);
+        Ok(Expression::List(elements))
+    }
+
+    fn parse_index_expression(
+        &mut self,
+        expression: Expression,
+    ) -> Result<Expression, ParseExpressionError> {
+        let start = self.last_span_start();
+        self.advance_token()?;
+        let index = self.with_no_in(false, |parser| parser.parse_expression(Precedence::Lowest))?;
+        self.advance_token()?;
+        if self.curr_token != Token::RBracket {
+            return Err(ParseExpressionError::ExpectedXFoundY {
+                expected: "Token::RBracket",
+                found: self.curr_token.clone(),
+            });
+        }
+        self.reduce_spans(2, start);
+        Ok(Expression::IndexExpression {
+            expression: Box::new(expression),
+            index: Box::new(index),
+        })
+    }
+
+    /// Parses the `.member` or `?.member` following `expression`.
+    fn parse_member_expression(
+        &mut self,
+        expression: Expression,
+    ) -> Result<Expression, ParseExpressionError> {
+        let start = self.last_span_start();
+        let null_safe = self.curr_token == Token::QuestionDot;
+        self.advance_token()?;
+        let Token::Ident(member) = &self.curr_token else {
+            return Err(ParseExpressionError::ExpectedXFoundY {
+                expected: "member name",
+                found: self.curr_token.clone(),
+            });
+        };
+        let member = member.clone();
+        self.reduce_spans(1, start);
+        Ok(Expression::MemberExpression {
+            expression: Box::new(expression),
+            member,
+            null_safe,
+        })
+    }
+
+    /// Parses `in(x, a, b)` into the same expression as `x in (a, b)`, and
+    /// `not in(x, a, b)` into the same expression as `x not in (a, b)`.
+    fn parse_in_function_call(&mut self) -> Result<Expression, ParseExpressionError> {
+        let operator = self.curr_token.clone();
+        let start = self.curr_span.start;
         self.advance_token()?;
+        if self.curr_token != Token::LParen {
+            return Err(ParseExpressionError::ExpectedXFoundY {
+                expected: "Token::LParen",
+                found: self.curr_token.clone(),
+            });
+        }
+        let mut arguments = self.parse_expression_list(Token::RParen)?.into_iter();
+        let Some(value) = arguments.next() else {
+            return Err(ParseExpressionError::ExpectedXFoundY {
+                expected: "value to look for",
+                found: self.curr_token.clone(),
+            });
+        };
+        let elements: Vec<Box<Expression>> = arguments.collect();
+        // The list has no parentheses of its own, it spans its elements.
+        let list_start = match elements.len() {
+            0 => self.curr_span.start,
+            count => self.spans[self.spans.len() - count].span.start,
+        };
+        self.reduce_spans(elements.len(), list_start);
+        self.reduce_spans(2, start);
+        Ok(Expression::InfixExpression {
+            operator,
+            left: value,
+            right: Box::new(Expression::List(elements)),
+        })
+    }
+
+    fn parse_function_call_expression(
+        &mut self,
+        function: Expression,
+    ) -> Result<Expression, ParseExpressionError> {
+        let start = self.last_span_start();
+        let args = self.parse_expression_list(Token::RParen)?;
+        // curr_token is Token::RParen, the last token of the call.
+        self.reduce_spans(args.len() + 1, start);
         Ok(Expression::CallExpression {
             function: Box::new(function),
             arguments: args,
@@ -124,45 +600,67 @@ impl Parser {
         precedence: Precedence,
     ) -> Result<Expression, ParseExpressionError> {
         let mut left_expression = match self.curr_token.clone() {
-            Token::IntLiteral(int) => Expression::Int(int),
-            Token::DoubleLiteral(double) => Expression::Double(double),
-            Token::True => Expression::Bool(true),
-            Token::False => Expression::Bool(false),
             Token::LBracket => self.parse_bracket_ident_expression()?,
             Token::LParen => self.parse_grouped_expression()?,
-            Token::Ident(ident) => Expression::Ident(ident.clone()),
-            Token::StringLiteral(string) => Expression::Str(string.clone()),
-            Token::Minus | Token::Not | Token::Bang => self.parse_prefix_expression()?,
-            _ => {
-                return Err(ParseExpressionError::UnexpectedToken {
-                    token: self.curr_token.clone(),
-                })
+            Token::LBrace => self.parse_list(Token::RBrace)?,
+            Token::In | Token::NotIn => self.parse_in_function_call()?,
+            Token::Let => self.parse_let_expression()?,
+            Token::Template(tokens) => self.parse_template(&tokens)?,
+            Token::Ident(ident) if *self.peek_next() == Token::Arrow => {
+                self.parse_lambda(vec![ident], self.curr_span.start)?
+            }
+            token if token.is_prefix_operator() => self.parse_prefix_expression()?,
+            token => {
+                let expression = match token {
+                    Token::IntLiteral(int) => Expression::Int(int),
+                    Token::DoubleLiteral(double) => Expression::Double(double),
+                    Token::DecimalLiteral(decimal) => Expression::Decimal(decimal),
+                    Token::BigIntLiteral(int) => Expression::BigInt(int),
+                    Token::True => Expression::Bool(true),
+                    Token::False => Expression::Bool(false),
+                    Token::Null => Expression::Null,
+                    Token::Ident(ident) => match self.peek_next() {
+                        Token::LParen => Expression::Ident(ident.clone()),
+                        _ => Expression::BareIdent(ident.clone()),
+                    },
+                    Token::StringLiteral(string) => Expression::Str(string.clone()),
+                    _ => return Err(ParseExpressionError::UnexpectedToken { token }),
+                };
+                self.push_leaf_span(self.curr_span);
+                expression
             }
         };
 
         while *self.peek_next() != Token::EOF
             && self.peek_next().get_precedence().value() > precedence.value()
+            && !(self.no_in && *self.peek_next() == Token::In)
         {
             self.advance_token()?;
             left_expression = match self.curr_token.clone() {
-                Token::Plus
-                | Token::Minus
-                | Token::Asterisk
-                | Token::Slash
-                | Token::Percent
-                | Token::Equals
-                | Token::NotEquals
-                | Token::NotEqualsAngleBrackets
-                | Token::LessThan
-                | Token::LessThanEqualTo
-                | Token::GreaterThan
-                | Token::GreaterThanEqualTo
-                | Token::Or
-                | Token::DoublePipe => self.parse_infix_expression(left_expression)?,
+                token if token.is_infix_operator() => {
+                    self.parse_infix_expression(left_expression)?
+                }
                 Token::LParen => self.parse_function_call_expression(left_expression)?,
+                Token::LBracket => self.parse_index_expression(left_expression)?,
+                Token::Dot | Token::QuestionDot => self.parse_member_expression(left_expression)?,
                 _ => unimplemented!(),
             }
         }
         Ok(left_expression)
     }
 }
+
+fn shift_spans(spans: &mut SpanTree, offset: usize) {
+    spans.span.start += offset;
+    spans.span.end += offset;
+    for child in &mut spans.children {
+        shift_spans(child, offset);
+    }
+}
+
+fn lambda_parameter(expression: Expression) -> Result<Rc<String>, ParseExpressionError> {
+    match expression {
+        Expression::BareIdent(name) => Ok(name),
+        found => Err(ParseExpressionError::ExpectedLambdaParameter { found }),
+    }
+}
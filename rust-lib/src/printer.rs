@@ -0,0 +1,281 @@
+use std::fmt;
+
+use num_traits::Signed;
+
+use crate::{
+    expression::{Expression, Precedence, Script, TemplatePart},
+    token::Token,
+};
+
+/// Writes the expression back as NCalc source.
+///
+/// The output is canonical: operators are surrounded by single spaces,
+/// arguments are separated by `", "` and parentheses are only added where the
+/// precedence of the operators requires them. Parsing the output gives back an
+/// expression equal to this one.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Nil => Ok(()),
+            Expression::Int(int) => write!(f, "{}", int),
+            Expression::Double(double) => write_double(f, *double),
+            Expression::Decimal(decimal) => write!(f, "{}m", decimal),
+            Expression::BigInt(int) => write!(f, "{}", int),
+            Expression::Bool(bool) => write!(f, "{}", bool),
+            Expression::Null => f.write_str("null"),
+            Expression::Ident(ident) => write!(f, "[{}]", ident),
+            Expression::BareIdent(ident) => write!(f, "{}", ident),
+            Expression::Str(string) => write_string(f, string),
+            Expression::PrefixExpression {
+                operator,
+                expression,
+            } => {
+                match operator {
+                    Token::Not => write!(f, "{} ", operator)?,
+                    _ => write!(f, "{}", operator)?,
+                }
+                write_operand(
+                    f,
+                    expression,
+                    binding_power(expression) < Precedence::Prefix.value(),
+                )
+            }
+            Expression::InfixExpression {
+                operator,
+                left,
+                right,
+            } => {
+                let precedence = operator.get_precedence().value();
+                // An operand of the same precedence only needs parentheses on
+                // the right, except for `**` which is right associative.
+                let is_right_associative = *operator == Token::DoubleAsterisk;
+                write_operand(
+                    f,
+                    left,
+                    binding_power(left) < precedence + i32::from(is_right_associative),
+                )?;
+                write!(f, " {} ", operator)?;
+                match (operator, right.as_ref()) {
+                    (Token::In | Token::NotIn, Expression::List(elements)) => {
+                        f.write_str("(")?;
+                        write_list(f, elements)?;
+                        f.write_str(")")
+                    }
+                    // There a `(` starts a list of values, so an operand that
+                    // needs parentheses, or starts with one, gets a second pair.
+                    (Token::In | Token::NotIn, right) => {
+                        let operand = right.to_string();
+                        match binding_power(right) <= precedence || operand.starts_with('(') {
+                            true => write!(f, "(({}))", operand),
+                            false => f.write_str(&operand),
+                        }
+                    }
+                    _ => write_operand(
+                        f,
+                        right,
+                        binding_power(right) < precedence + i32::from(!is_right_associative),
+                    ),
+                }
+            }
+            Expression::CallExpression {
+                function,
+                arguments,
+            } => {
+                match function.as_ref() {
+                    Expression::Ident(function_name) => write!(f, "{}", function_name)?,
+                    function => write_operand(
+                        f,
+                        function,
+                        binding_power(function) < Precedence::Call.value(),
+                    )?,
+                }
+                f.write_str("(")?;
+                write_list(f, arguments)?;
+                f.write_str(")")
+            }
+            Expression::List(elements) => {
+                f.write_str("{")?;
+                write_list(f, elements)?;
+                f.write_str("}")
+            }
+            Expression::Lambda { parameters, body } => {
+                match parameters.as_slice() {
+                    [parameter] => write!(f, "{}", parameter)?,
+                    parameters => {
+                        f.write_str("(")?;
+                        for (i, parameter) in parameters.iter().enumerate() {
+                            if i > 0 {
+                                f.write_str(", ")?;
+                            }
+                            write!(f, "{}", parameter)?;
+                        }
+                        f.write_str(")")?;
+                    }
+                }
+                write!(f, " => {}", body)
+            }
+            Expression::IndexExpression { expression, index } => {
+                write_operand(
+                    f,
+                    expression,
+                    binding_power(expression) < Precedence::Call.value(),
+                )?;
+                write!(f, "[{}]", index)
+            }
+            Expression::MemberExpression {
+                expression,
+                member,
+                null_safe,
+            } => {
+                // The '.' would be read as part of a number literal.
+                let is_number = !null_safe
+                    && matches!(
+                        expression.as_ref(),
+                        Expression::Int(_)
+                            | Expression::Double(_)
+                            | Expression::BigInt(_)
+                            | Expression::Decimal(_)
+                    );
+                write_operand(
+                    f,
+                    expression,
+                    is_number || binding_power(expression) < Precedence::Call.value(),
+                )?;
+                match null_safe {
+                    true => write!(f, "?.{}", member),
+                    false => write!(f, ".{}", member),
+                }
+            }
+            Expression::Template(parts) => {
+                f.write_str("$\"")?;
+                for part in parts {
+                    match part {
+                        TemplatePart::Text(text) => write_string_contents(f, text, true)?,
+                        TemplatePart::Value {
+                            expression,
+                            format: Some(format),
+                        } => write!(f, "{{{}:{}}}", expression, format)?,
+                        TemplatePart::Value { expression, .. } => write!(f, "{{{}}}", expression)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            Expression::Let { name, value, body } => {
+                write!(f, "let {} = ", name)?;
+                write_operand(f, value, has_unbracketed_in(value))?;
+                write!(f, " in {}", body)
+            }
+        }
+    }
+}
+
+/// Writes the script back as source, one `[target] = value;` statement per
+/// line.
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, statement) in self.statements.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "[{}] = {};", statement.target, statement.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// How tightly `expression` holds together when it is the operand of another
+/// expression, on the same scale as [`Precedence::value`].
+fn binding_power(expression: &Expression) -> i32 {
+    match expression {
+        Expression::InfixExpression { operator, .. } => operator.get_precedence().value(),
+        Expression::PrefixExpression { .. } => Precedence::Prefix.value(),
+        // The body of a lambda or a `let` extends as far to the right as it can.
+        Expression::Lambda { .. } | Expression::Let { .. } => Precedence::Lowest.value(),
+        // A negative literal is written with a leading '-', which the parser
+        // reads as a prefix expression.
+        Expression::Int(int) if *int < 0 => Precedence::Prefix.value(),
+        Expression::Double(double) if double.is_sign_negative() => Precedence::Prefix.value(),
+        Expression::Decimal(decimal) if decimal.is_sign_negative() => Precedence::Prefix.value(),
+        Expression::BigInt(int) if int.is_negative() => Precedence::Prefix.value(),
+        _ => Precedence::Call.value(),
+    }
+}
+
+/// Whether the source of `expression` has an `in` operator outside of any
+/// brackets, which would end the value of a `let` early.
+fn has_unbracketed_in(expression: &Expression) -> bool {
+    match expression {
+        Expression::InfixExpression {
+            operator: Token::In,
+            ..
+        } => true,
+        Expression::InfixExpression { left, right, .. } => {
+            has_unbracketed_in(left) || has_unbracketed_in(right)
+        }
+        Expression::PrefixExpression { expression, .. }
+        | Expression::IndexExpression { expression, .. }
+        | Expression::MemberExpression { expression, .. } => has_unbracketed_in(expression),
+        Expression::CallExpression { function, .. } => has_unbracketed_in(function),
+        Expression::Lambda { body, .. } | Expression::Let { body, .. } => has_unbracketed_in(body),
+        _ => false,
+    }
+}
+
+fn write_operand(
+    f: &mut fmt::Formatter<'_>,
+    operand: &Expression,
+    parenthesize: bool,
+) -> fmt::Result {
+    match parenthesize {
+        true => write!(f, "({})", operand),
+        false => write!(f, "{}", operand),
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, expressions: &[Box<Expression>]) -> fmt::Result {
+    for (i, expression) in expressions.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", expression)?;
+    }
+    Ok(())
+}
+
+fn write_double(f: &mut fmt::Formatter<'_>, double: f64) -> fmt::Result {
+    // `Display` for f64 never uses an exponent, but drops the fractional part
+    // of whole numbers, which would then be read back as an int.
+    let text = double.to_string();
+    match double.is_finite() && !text.contains('.') {
+        true => write!(f, "{}.0", text),
+        false => f.write_str(&text),
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    write_string_contents(f, string, false)?;
+    f.write_str("\"")
+}
+
+/// Writes `string` escaped for the inside of a string literal, or of a
+/// template literal where braces are doubled.
+fn write_string_contents(
+    f: &mut fmt::Formatter<'_>,
+    string: &str,
+    is_template: bool,
+) -> fmt::Result {
+    for ch in string.chars() {
+        match ch {
+            '{' if is_template => f.write_str("{{")?,
+            '}' if is_template => f.write_str("}}")?,
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            '\r' => f.write_str("\\r")?,
+            _ => write!(f, "{}", ch)?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{
+    analysis,
+    expression::{Expression, ParseExpressionError},
+    lexer::Lexer,
+    object::EvalOptions,
+    parser,
+    visitor::{fold_lambda, walk_lambda, walk_let, Fold, Visitor},
+};
+
+/// What a parameter is replaced with by [`substitute`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Substitution {
+    /// Another parameter name, written the same way (with or without brackets).
+    Rename(String),
+    Replace(Expression),
+}
+
+#[derive(Debug)]
+pub enum SubstitutionError {
+    ParseExpressionError { error: ParseExpressionError },
+    InvalidIdentifier(String),
+}
+
+/// Parses `input`, applies [`substitute`] and prints the result back to source.
+pub fn substitute_input(
+    input: &str,
+    substitutions: &HashMap<String, Substitution>,
+) -> Result<String, SubstitutionError> {
+    substitute_input_with(input, substitutions, &EvalOptions::default())
+}
+
+/// Like [`substitute_input`], reading the input the way `options` ask, see
+/// [`parser::parse_input_with`].
+pub fn substitute_input_with(
+    input: &str,
+    substitutions: &HashMap<String, Substitution>,
+    options: &EvalOptions,
+) -> Result<String, SubstitutionError> {
+    let expression = parser::parse_input_with(input, options)
+        .map_err(|error| SubstitutionError::ParseExpressionError { error })?;
+    Ok(substitute(expression, substitutions)?.to_string())
+}
+
+/// Replaces the parameters of `expression` named in `substitutions`.
+///
+/// Function names are left alone, and a replacement expression is inserted as
+/// a whole: it is not substituted again and keeps its own grouping, so that
+/// `[a] * [b]` with `[b]` replaced by `[c] + 1` becomes `[a] * ([c] + 1)`.
+/// The parameters of a lambda, and their uses in its body, are not substituted,
+/// and neither are the names bound by a `let`. Such a name is renamed when it
+/// would capture a name brought in by a substitution: renaming `rate` to `x` in
+/// `map([items], x => x * [rate])` gives `map([items], x_1 => x_1 * [x])`.
+pub fn substitute(
+    expression: Expression,
+    substitutions: &HashMap<String, Substitution>,
+) -> Result<Expression, SubstitutionError> {
+    for substitution in substitutions.values() {
+        if let Substitution::Rename(name) = substitution {
+            if !Lexer::is_identifier(name) {
+                return Err(SubstitutionError::InvalidIdentifier(name.clone()));
+            }
+        }
+    }
+    let mut names = Names::default();
+    names.visit_expression(&expression);
+    for (name, substitution) in substitutions {
+        names.0.insert(name.clone());
+        match substitution {
+            Substitution::Rename(name) => {
+                names.0.insert(name.clone());
+            }
+            Substitution::Replace(expression) => names.visit_expression(expression),
+        }
+    }
+    Ok(Substituter {
+        substitutions,
+        bound: vec![],
+        taken: names.0,
+    }
+    .fold_expression(expression))
+}
+
+struct Substituter<'a> {
+    substitutions: &'a HashMap<String, Substitution>,
+    // Parameters of the lambdas and names of the lets being folded, along with
+    // the names they are written with in the result.
+    bound: Vec<(Rc<String>, Rc<String>)>,
+    // Names a renamed parameter or let name must not collide with.
+    taken: HashSet<String>,
+}
+
+impl Substituter<'_> {
+    fn substitute(
+        &self,
+        ident: Rc<String>,
+        make_ident: fn(Rc<String>) -> Expression,
+    ) -> Expression {
+        if let Some((_, renamed)) = self.bound.iter().rev().find(|(name, _)| *name == ident) {
+            return make_ident(renamed.clone());
+        }
+        match self.substitutions.get(ident.as_str()) {
+            Some(Substitution::Rename(name)) => make_ident(Rc::new(name.clone())),
+            Some(Substitution::Replace(expression)) => expression.clone(),
+            None => make_ident(ident),
+        }
+    }
+
+    /// Returns what to write `names`, bound together over `body`, as in the
+    /// result. A name is kept unless a substitution within `body` brings in a
+    /// reference to it, which would then refer to the binding instead.
+    fn bind(&mut self, names: &[Rc<String>], body: &Expression) -> Vec<Rc<String>> {
+        let references = analysis::references(body);
+        let substituted: Vec<_> = references
+            .parameters
+            .iter()
+            .chain(&references.identifiers)
+            .filter(|name| {
+                !names.iter().any(|bound| bound.as_str() == name.as_str())
+                    && !self
+                        .bound
+                        .iter()
+                        .any(|(bound, _)| bound.as_str() == name.as_str())
+            })
+            .filter_map(|name| self.substitutions.get(name))
+            .collect();
+        names
+            .iter()
+            .map(|name| {
+                let captures = substituted.iter().any(|substitution| match substitution {
+                    Substitution::Rename(target) => target == name.as_str(),
+                    Substitution::Replace(expression) => {
+                        let references = analysis::references(expression);
+                        references.parameters.contains(name.as_str())
+                            || references.identifiers.contains(name.as_str())
+                    }
+                });
+                if captures {
+                    self.fresh_name(name)
+                } else {
+                    name.clone()
+                }
+            })
+            .collect()
+    }
+
+    fn fresh_name(&mut self, name: &str) -> Rc<String> {
+        let mut suffix = 1;
+        while self.taken.contains(&format!("{name}_{suffix}")) {
+            suffix += 1;
+        }
+        let fresh = format!("{name}_{suffix}");
+        self.taken.insert(fresh.clone());
+        Rc::new(fresh)
+    }
+}
+
+impl Fold for Substituter<'_> {
+    fn fold_lambda(&mut self, parameters: Vec<Rc<String>>, body: Expression) -> Expression {
+        let count = parameters.len();
+        let renamed = self.bind(&parameters, &body);
+        self.bound
+            .extend(parameters.into_iter().zip(renamed.iter().cloned()));
+        let lambda = fold_lambda(self, renamed, body);
+        self.bound.truncate(self.bound.len() - count);
+        lambda
+    }
+
+    fn fold_let(&mut self, name: Rc<String>, value: Expression, body: Expression) -> Expression {
+        let value = self.fold_expression(value);
+        let renamed = self.bind(std::slice::from_ref(&name), &body).remove(0);
+        self.bound.push((name, renamed.clone()));
+        let body = self.fold_expression(body);
+        self.bound.pop();
+        Expression::Let {
+            name: renamed,
+            value: Box::new(value),
+            body: Box::new(body),
+        }
+    }
+
+    fn fold_ident(&mut self, ident: Rc<String>) -> Expression {
+        self.substitute(ident, Expression::Ident)
+    }
+
+    fn fold_bare_ident(&mut self, ident: Rc<String>) -> Expression {
+        self.substitute(ident, Expression::BareIdent)
+    }
+}
+
+/// Collects every name written in an expression, bound or not.
+#[derive(Default)]
+struct Names(HashSet<String>);
+
+impl Visitor for Names {
+    fn visit_lambda(&mut self, parameters: &[Rc<String>], body: &Expression) {
+        self.0
+            .extend(parameters.iter().map(|parameter| parameter.to_string()));
+        walk_lambda(self, parameters, body);
+    }
+
+    fn visit_let(&mut self, name: &Rc<String>, value: &Expression, body: &Expression) {
+        self.0.insert(name.to_string());
+        walk_let(self, name, value, body);
+    }
+
+    fn visit_ident(&mut self, ident: &Rc<String>) {
+        self.0.insert(ident.to_string());
+    }
+
+    fn visit_bare_ident(&mut self, ident: &Rc<String>) {
+        self.0.insert(ident.to_string());
+    }
+}
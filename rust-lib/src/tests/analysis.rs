@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    analysis::{references, FunctionReference},
+    parser::parse_input,
+};
+
+fn names(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn collects_parameters_identifiers_and_functions() {
+    let expression = parse_input("if([a] > b, max([a], 2) * [c], if(true, [a], 0))").unwrap();
+    let references = references(&expression);
+
+    assert_eq!(names(&["a", "c"]), references.parameters);
+    assert_eq!(names(&["b"]), references.identifiers);
+    assert_eq!(
+        BTreeSet::from_iter([
+            FunctionReference {
+                name: "if".to_string(),
+                arity: 3,
+            },
+            FunctionReference {
+                name: "max".to_string(),
+                arity: 2,
+            },
+        ]),
+        references.functions
+    );
+}
+
+#[test]
+fn lambda_parameters_are_not_references() {
+    let expression = parse_input("reduce([items], 0, (acc, x) => acc + x * rate) + x").unwrap();
+    let references = references(&expression);
+    assert_eq!(names(&["items"]), references.parameters);
+    assert_eq!(names(&["rate", "x"]), references.identifiers);
+}
+
+#[test]
+fn let_names_are_not_references() {
+    let expression =
+        parse_input("let net = [price] * (1 - [discount]) in net + net * [tax] + other").unwrap();
+    let found = references(&expression);
+    assert_eq!(names(&["discount", "price", "tax"]), found.parameters);
+    assert_eq!(names(&["other"]), found.identifiers);
+
+    // The value is outside the scope of its own name.
+    let found = references(&parse_input("let x = x + 1 in x").unwrap());
+    assert_eq!(names(&["x"]), found.identifiers);
+}
+
+#[test]
+fn method_calls_reference_functions() {
+    let expression = parse_input("[name].Trim().ToLower(1).Length").unwrap();
+    let references = references(&expression);
+    assert_eq!(names(&["name"]), references.parameters);
+    assert_eq!(
+        BTreeSet::from_iter([
+            FunctionReference {
+                name: "ToLower".to_string(),
+                arity: 2,
+            },
+            FunctionReference {
+                name: "Trim".to_string(),
+                arity: 1,
+            },
+        ]),
+        references.functions
+    );
+}
+
+#[test]
+fn literals_reference_nothing() {
+    let references = references(&parse_input("1 + 2.5 * -3 == 0 or \"x\" == \"y\"").unwrap());
+    assert!(references.parameters.is_empty());
+    assert!(references.identifiers.is_empty());
+    assert!(references.functions.is_empty());
+}
@@ -0,0 +1,164 @@
+use std::rc::Rc;
+
+use crate::{
+    binary::{decode, encode, BinaryError, FORMAT_VERSION},
+    expression::Expression,
+    parser::parse_input,
+    token::Token,
+};
+
+#[test]
+fn expressions_round_trip() {
+    let tests = vec![
+        "1",
+        "-9223372036854775807 - 1",
+        "1.5 + [x] * 0.1",
+        "if([price] > 100 and not [vip], [price] * 0.9, [price]) <> x",
+        "concat(\"é\", \"\", \"\\\"\") || !false % 3",
+        "f()",
+        "coalesce([x], null)",
+        "[x] in (1, \"a\") or y not in ()",
+        "{1, {}}[0] in [list]",
+        "[customer].address.city == map([orders], o => o.total)[0]",
+        "[customer]?.address?.city ?? [x]?.Trim()",
+        "let net = [price] * 2, tax = net * 0.2 in net + tax",
+        "2 ** [x] ** -0.5",
+        "0.10m * -3m + 0.0000000000000000000000000001m",
+        "-9223372036854775808 + 18446744073709551616 * 170141183460469231731687303715884105728",
+        "$\"{{{[id]}}} costs {[price] * 2:N2}.\" + $\"\"",
+    ]
+    .into_iter();
+
+    for input in tests {
+        let expression = parse_input(input).unwrap();
+        let bytes = encode(&expression).unwrap();
+        assert_eq!(expression, decode(&bytes).unwrap(), "{input}");
+    }
+}
+
+#[test]
+fn long_chains_round_trip() {
+    let chain = |operands: usize, operator: &str| {
+        (0..operands)
+            .map(|operand| format!("[a{operand}]"))
+            .collect::<Vec<_>>()
+            .join(operator)
+    };
+    for input in [
+        chain(300, " + "),
+        chain(300, " - ") + " * 2 + " + &chain(300, " * "),
+    ] {
+        let expression = parse_input(&input).unwrap();
+        let bytes = encode(&expression).unwrap();
+        assert_eq!(expression, decode(&bytes).unwrap());
+    }
+
+    // Nesting anywhere else still counts every level.
+    let expression = (0..300).fold(Expression::Int(1), |expression, _| {
+        Expression::PrefixExpression {
+            operator: Token::Minus,
+            expression: Box::new(expression),
+        }
+    });
+    assert_eq!(Err(BinaryError::TooDeep), encode(&expression));
+}
+
+#[test]
+fn identifiers_are_stored_once() {
+    let expression = parse_input("[price] * [price] + price(1, [price])").unwrap();
+    let bytes = encode(&expression).unwrap();
+    assert_eq!(b"NCLC", &bytes[..4]);
+    assert_eq!(FORMAT_VERSION, bytes[4]);
+    // One string in the table: "price", 5 bytes long.
+    assert_eq!(&[1, 5], &bytes[5..7]);
+    assert_eq!(1, bytes.windows(5).filter(|w| w == b"price").count());
+
+    let Expression::InfixExpression { left, .. } = decode(&bytes).unwrap() else {
+        panic!("expected an infix expression");
+    };
+    let Expression::InfixExpression { left, right, .. } = *left else {
+        panic!("expected an infix expression");
+    };
+    let (Expression::Ident(left), Expression::Ident(right)) = (*left, *right) else {
+        panic!("expected identifiers");
+    };
+    assert!(Rc::ptr_eq(&left, &right));
+}
+
+#[test]
+fn rejects_corrupt_input() {
+    let bytes = encode(&parse_input("[a] + 1").unwrap()).unwrap();
+
+    assert_eq!(Err(BinaryError::BadMagic), decode(b"NCL"));
+    assert_eq!(Err(BinaryError::BadMagic), decode(b"XCLC\x01"));
+    assert_eq!(
+        Err(BinaryError::UnsupportedVersion(99)),
+        decode(b"NCLC\x63\x00\x00")
+    );
+    assert_eq!(
+        Err(BinaryError::UnexpectedEnd),
+        decode(&bytes[..bytes.len() - 1])
+    );
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(Err(BinaryError::TrailingBytes), decode(&trailing));
+    assert_eq!(Err(BinaryError::UnExpectedNil), decode(b"NCLC\x01\x00\x00"));
+    assert_eq!(
+        Err(BinaryError::InvalidTag(200)),
+        decode(b"NCLC\x01\x00\xc8")
+    );
+    assert_eq!(
+        Err(BinaryError::InvalidStringIndex(3)),
+        decode(b"NCLC\x01\x00\x05\x03")
+    );
+    assert_eq!(
+        Err(BinaryError::InvalidUtf8),
+        decode(b"NCLC\x01\x01\x01\xff\x07\x00")
+    );
+    // A decimal with a scale of 29.
+    assert_eq!(
+        Err(BinaryError::InvalidDecimal),
+        decode(b"NCLC\x01\x00\x12\x00\x00\x1d\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00")
+    );
+    // 5 fits in an int, and 2 ** 63 has a redundant sign byte.
+    assert_eq!(
+        Err(BinaryError::InvalidBigInt),
+        decode(b"NCLC\x01\x00\x13\x01\x05")
+    );
+    assert_eq!(
+        Err(BinaryError::InvalidBigInt),
+        decode(b"NCLC\x01\x00\x13\x0a\x00\x00\x00\x00\x00\x00\x00\x80\x00\x00")
+    );
+    // '+' (code 5) is not a prefix operator.
+    assert_eq!(
+        Err(BinaryError::InvalidOperator(5)),
+        decode(b"NCLC\x01\x00\x08\x05\x01\x02")
+    );
+    let mut deep = b"NCLC\x01\x00".to_vec();
+    deep.extend(std::iter::repeat_n([8u8, 6], 1000).flatten());
+    deep.extend([1, 2]);
+    assert_eq!(Err(BinaryError::TooDeep), decode(&deep));
+}
+
+#[test]
+fn rejects_non_operator_tokens() {
+    let expression = Expression::PrefixExpression {
+        operator: Token::Comma,
+        expression: Box::new(Expression::Int(1)),
+    };
+    assert_eq!(
+        Err(BinaryError::UnsupportedToken(Token::Comma)),
+        encode(&expression)
+    );
+}
+
+#[test]
+fn rejects_nil() {
+    assert_eq!(Err(BinaryError::UnExpectedNil), encode(&Expression::Nil));
+    let expression = Expression::InfixExpression {
+        operator: Token::Plus,
+        left: Box::new(Expression::Int(1)),
+        right: Box::new(Expression::Nil),
+    };
+    assert_eq!(Err(BinaryError::UnExpectedNil), encode(&expression));
+}
@@ -1,13 +1,41 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
+
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
 
 use crate::{
-    evaluator,
-    object::{Context, Object},
+    evaluator::{self, EvalError},
+    object::{Context, DivisionByZeroPolicy, EvalOptions, MissingParameterPolicy, Object},
+    token::Token,
 };
 
 #[test]
 fn basic_eval_tests() {
-    let tests = [("!true", Object::Bool(false))].into_iter();
+    let tests = [
+        ("!true", Object::Bool(false)),
+        ("1 <> 2", Object::Bool(true)),
+        ("true <> true", Object::Bool(false)),
+        ("7 % 4 * 2", Object::Double(6.0)),
+        ("-2 * 3", Object::Double(-6.0)),
+        ("not false or false", Object::Bool(true)),
+        ("not (false or true)", Object::Bool(false)),
+        ("2 ** 3 ** 2", Object::Int(512)),
+        ("-2 ** 3", Object::Int(-8)),
+        ("2 ** -1", Object::Double(0.5)),
+        ("4 ** 0.5", Object::Double(2.0)),
+        ("1.5 ** 2", Object::Double(2.25)),
+        ("2 ** 64", Object::Double(18446744073709551616.0)),
+        ("2 ** null", Object::Null),
+        ("7 // 2", Object::Int(3)),
+        ("-7 // 2", Object::Int(-3)),
+        ("7 % -2", Object::Int(1)),
+        ("-7 % 2", Object::Int(-1)),
+        ("-7 // 2 * 2 + -7 % 2", Object::Double(-7.0)),
+        ("7.5 // 2", Object::Double(3.0)),
+        ("-7.5 % 2", Object::Double(-1.5)),
+        ("null // 0", Object::Null),
+    ]
+    .into_iter();
     for (test, expected) in tests {
         assert_eq!(
             expected,
@@ -16,6 +44,39 @@ fn basic_eval_tests() {
     }
 }
 
+#[test]
+fn integer_division_edge_cases() {
+    for input in ["1 // 0", "1 % 0", "[x] // 0 + 1"] {
+        assert!(
+            matches!(
+                evaluator::eval_input(
+                    input,
+                    Context::from_map(HashMap::from_iter([("x".to_string(), Object::Int(1))]))
+                ),
+                Err(EvalError::DivideByZero)
+            ),
+            "{input}"
+        );
+    }
+    assert_eq!(
+        Object::Double(f64::INFINITY),
+        evaluator::eval_input("1.0 // 0", Context::default()).unwrap()
+    );
+    // The one quotient of integers that does not fit in an Int.
+    let context = Context::from_map(HashMap::from_iter([(
+        "min".to_string(),
+        Object::Int(i64::MIN),
+    )]));
+    assert_eq!(
+        Object::Double(9223372036854775808.0),
+        evaluator::eval_input("[min] // -1", context.clone()).unwrap()
+    );
+    assert_eq!(
+        Object::Int(0),
+        evaluator::eval_input("[min] % -1", context).unwrap()
+    );
+}
+
 #[test]
 fn evaluate_ident_for_expr() {
     let test = "[x] + 5";
@@ -191,3 +252,1088 @@ fn evaluate_if_function() {
         assert_eq!(expected, evaluator::eval_input(test, context).unwrap());
     }
 }
+
+#[test]
+fn evaluate_logical_operators() {
+    let tests = vec![
+        ("true and false", Object::Bool(false)),
+        ("true && true", Object::Bool(true)),
+        ("false or true", Object::Bool(true)),
+        ("1 < 2 and 2 < 3 or false", Object::Bool(true)),
+        // The right operand is not evaluated once the left one decides the result.
+        ("false and [missing]", Object::Bool(false)),
+        ("true || [missing]", Object::Bool(true)),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, Context::default()).unwrap()
+        );
+    }
+}
+
+#[test]
+fn evaluate_bare_ident() {
+    let context = Context::from_map(HashMap::from_iter([("x".to_string(), Object::Int(2))]));
+    assert_eq!(
+        Object::Double(4.0),
+        evaluator::eval_input("x * [x]", context).unwrap()
+    );
+}
+
+#[test]
+fn null_propagates_through_operators() {
+    let tests = vec![
+        ("null", Object::Null),
+        ("null + 1", Object::Null),
+        ("-null * 2", Object::Null),
+        ("1 < null", Object::Null),
+        ("null == null", Object::Null),
+        ("not null", Object::Null),
+        // Three-valued logic: null only decides the result when the other
+        // operand does not.
+        ("null and false", Object::Bool(false)),
+        ("null and true", Object::Null),
+        ("null or true", Object::Bool(true)),
+        ("false or null", Object::Null),
+        ("if(null, 1, 2)", Object::Int(2)),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, Context::default()).unwrap(),
+            "{test}"
+        );
+    }
+}
+
+#[test]
+fn evaluate_null_functions() {
+    let context = Context::from_map(HashMap::from_iter([
+        ("x".to_string(), Object::Null),
+        ("y".to_string(), Object::Int(3)),
+    ]));
+    let tests = vec![
+        ("isnull([x])", Object::Bool(true)),
+        ("isnull([y])", Object::Bool(false)),
+        ("ifnull([x], 1)", Object::Int(1)),
+        ("ifnull([y], [missing])", Object::Int(3)),
+        ("coalesce([x], null, [y], [missing])", Object::Int(3)),
+        ("coalesce()", Object::Null),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, context.clone()).unwrap(),
+            "{test}"
+        );
+    }
+    assert!(matches!(
+        evaluator::eval_input("nope(1)", context),
+        Err(EvalError::UnknownFunction(name)) if name == "nope"
+    ));
+}
+
+#[test]
+fn evaluate_null_coalescing() {
+    let context = Context::from_map(HashMap::from_iter([
+        ("x".to_string(), Object::Null),
+        ("y".to_string(), Object::Int(3)),
+    ]));
+    let tests = vec![
+        ("[x] ?? 1", Object::Int(1)),
+        ("[y] ?? [missing]", Object::Int(3)),
+        ("[x] ?? null ?? [y]", Object::Int(3)),
+        ("[x] ?? 1 + 1", Object::Double(2.0)),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, context.clone()).unwrap(),
+            "{test}"
+        );
+    }
+}
+
+#[test]
+fn missing_parameter_policy() {
+    assert!(matches!(
+        evaluator::eval_input("[discount] ?? 0", Context::default()),
+        Err(EvalError::ReferenceNotFound(name)) if name == "discount"
+    ));
+
+    let context = Context::default().with_options(EvalOptions {
+        missing_parameter: MissingParameterPolicy::Null,
+        ..EvalOptions::default()
+    });
+    assert_eq!(
+        Object::Int(0),
+        evaluator::eval_input("[discount] ?? 0", context.clone()).unwrap()
+    );
+    assert_eq!(
+        Object::Bool(true),
+        evaluator::eval_input("isnull(discount)", context).unwrap()
+    );
+}
+
+#[test]
+fn division_by_zero_policy() {
+    let with_policy = |division_by_zero| {
+        Context::default().with_options(EvalOptions {
+            division_by_zero,
+            ..EvalOptions::default()
+        })
+    };
+    let context = with_policy(DivisionByZeroPolicy::Ieee);
+    assert_eq!(
+        Object::Double(f64::NEG_INFINITY),
+        evaluator::eval_input("-1 / 0", context.clone()).unwrap()
+    );
+    assert!(matches!(
+        evaluator::eval_input("0 / 0.0", context.clone()),
+        Ok(Object::Double(double)) if double.is_nan()
+    ));
+    assert!(matches!(
+        evaluator::eval_input("1 % 0", context),
+        Err(EvalError::DivideByZero)
+    ));
+
+    let context = with_policy(DivisionByZeroPolicy::Error);
+    for input in [
+        "1 / 0",
+        "0 / 0.0",
+        "1.5 // -0.0",
+        "1 % 0",
+        "2 + 1 / (1 - 1)",
+    ] {
+        assert!(
+            matches!(
+                evaluator::eval_input(input, context.clone()),
+                Err(EvalError::DivideByZero)
+            ),
+            "{input}"
+        );
+    }
+    assert_eq!(
+        Object::Double(0.5),
+        evaluator::eval_input("1 / 2", context).unwrap()
+    );
+
+    let context = with_policy(DivisionByZeroPolicy::Null);
+    for input in ["1 / 0", "0.0 // 0", "7 % 0", "(1 / 0) * 2"] {
+        assert_eq!(
+            Object::Null,
+            evaluator::eval_input(input, context.clone()).unwrap(),
+            "{input}"
+        );
+    }
+    assert_eq!(
+        Object::Int(5),
+        evaluator::eval_input("1 / 0 ?? 5", context).unwrap()
+    );
+}
+
+#[test]
+fn non_finite_results_can_be_rejected() {
+    let context = Context::from_map(HashMap::from_iter([
+        ("big".to_string(), Object::Double(f64::MAX)),
+        ("nan".to_string(), Object::Double(f64::NAN)),
+    ]))
+    .with_options(EvalOptions {
+        reject_non_finite: true,
+        ..EvalOptions::default()
+    });
+    let tests = vec![
+        "1 / 0",
+        "[big] * 2",
+        "Sum([big], [big])",
+        "(-8) ** 0.5",
+        "[nan] + 1",
+    ]
+    .into_iter();
+    for input in tests {
+        assert!(
+            matches!(
+                evaluator::eval_input(input, context.clone()),
+                Err(EvalError::NonFiniteResult(_))
+            ),
+            "{input}"
+        );
+    }
+    // A NaN passed in is only rejected once an operator or function uses it.
+    assert!(matches!(
+        evaluator::eval_input("[nan]", context.clone()),
+        Ok(Object::Double(double)) if double.is_nan()
+    ));
+    assert_eq!(
+        Object::Double(2.0),
+        evaluator::eval_input("[big] / [big] * 2", context).unwrap()
+    );
+}
+
+#[test]
+fn evaluate_in_operator() {
+    let context = Context::from_map(HashMap::from_iter([
+        (
+            "country".to_string(),
+            Object::Str(Rc::new("CA".to_string())),
+        ),
+        ("qty".to_string(), Object::Int(2)),
+    ]));
+    let tests = vec![
+        ("[country] in (\"US\", \"CA\")", Object::Bool(true)),
+        ("[country] not in (\"US\", \"CA\")", Object::Bool(false)),
+        ("in([country], \"FR\")", Object::Bool(false)),
+        ("not in([qty], 1, 3)", Object::Bool(true)),
+        ("[qty] in (2.0)", Object::Bool(true)),
+        ("[qty] + 1 in (1, 3) and true", Object::Bool(true)),
+        ("[qty] in ()", Object::Bool(false)),
+        // Elements after the first match are not evaluated.
+        ("[qty] in (2, [missing])", Object::Bool(true)),
+        ("[qty] in (1, null)", Object::Null),
+        ("[qty] not in (1, null)", Object::Null),
+        ("null in (1, 2)", Object::Null),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, context.clone()).unwrap(),
+            "{test}"
+        );
+    }
+    assert!(matches!(
+        evaluator::eval_input("[country] in (1)", context),
+        Err(EvalError::UnExpectedOperatorOperandFound { .. })
+    ));
+}
+
+#[test]
+fn evaluate_lists() {
+    let list = |objects: Vec<Object>| Object::List(Rc::new(objects));
+    let context = Context::from_map(HashMap::from_iter([(
+        "tags".to_string(),
+        list(vec![
+            Object::Str(Rc::new("a".to_string())),
+            Object::Str(Rc::new("b".to_string())),
+        ]),
+    )]));
+    let tests = vec![
+        ("{}", list(vec![])),
+        (
+            "{1, 1 + 1}",
+            list(vec![Object::Int(1), Object::Double(2.0)]),
+        ),
+        ("{1, {2}}[1][0]", Object::Int(2)),
+        ("[tags][1]", Object::Str(Rc::new("b".to_string()))),
+        ("tags[2 - 1] == \"b\"", Object::Bool(true)),
+        ("\"b\" in [tags]", Object::Bool(true)),
+        ("\"c\" not in tags", Object::Bool(true)),
+        ("{1, 2} == {1, 2.0}", Object::Bool(true)),
+        ("{1, 2} != {1}", Object::Bool(true)),
+        ("{1, null} == {1, 2}", Object::Null),
+        ("null[0]", Object::Null),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, context.clone()).unwrap(),
+            "{test}"
+        );
+    }
+    assert!(matches!(
+        evaluator::eval_input("[tags][2]", context.clone()),
+        Err(EvalError::IndexOutOfBounds { index: 2, len: 2 })
+    ));
+    assert!(matches!(
+        evaluator::eval_input("[tags][0.5]", context),
+        Err(EvalError::ExpectedObjectXFoundY {
+            expected: "int",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn evaluate_aggregates() {
+    let list = |objects: Vec<Object>| Object::List(Rc::new(objects));
+    let context = Context::from_map(HashMap::from_iter([(
+        "prices".to_string(),
+        list(vec![
+            Object::Int(3),
+            Object::Double(1.5),
+            Object::Null,
+            Object::Int(6),
+        ]),
+    )]));
+    let tests = vec![
+        ("Sum([prices])", Object::Double(10.5)),
+        ("Avg([prices])", Object::Double(3.5)),
+        ("Count([prices])", Object::Int(4)),
+        ("Min([prices])", Object::Double(1.5)),
+        ("Max([prices])", Object::Int(6)),
+        ("Max(1, 4, 2)", Object::Int(4)),
+        ("Sum({})", Object::Double(0.0)),
+        ("Avg({})", Object::Null),
+        ("Max(null)", Object::Null),
+        (
+            "Distinct({1, 1.0, null, 2, null})",
+            list(vec![Object::Int(1), Object::Null, Object::Int(2)]),
+        ),
+        (
+            "Sort([prices])",
+            list(vec![
+                Object::Double(1.5),
+                Object::Int(3),
+                Object::Int(6),
+                Object::Null,
+            ]),
+        ),
+        (
+            "Sort(\"b\", \"a\")",
+            list(vec![
+                Object::Str(Rc::new("a".to_string())),
+                Object::Str(Rc::new("b".to_string())),
+            ]),
+        ),
+        ("Any({false, null, true})", Object::Bool(true)),
+        ("Any({false, null})", Object::Null),
+        ("All({true, true})", Object::Bool(true)),
+        ("All({})", Object::Bool(true)),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, context.clone()).unwrap(),
+            "{test}"
+        );
+    }
+    for test in ["Sum({1, \"a\"})", "Sort({1, \"a\"})", "All({1})"] {
+        assert!(
+            matches!(
+                evaluator::eval_input(test, context.clone()),
+                Err(EvalError::ExpectedObjectXFoundY { .. })
+            ),
+            "{test}"
+        );
+    }
+}
+
+#[test]
+fn evaluate_lambda_functions() {
+    let list = |objects: Vec<Object>| Object::List(Rc::new(objects));
+    let context = Context::from_map(HashMap::from_iter([
+        (
+            "items".to_string(),
+            list(vec![Object::Int(5), Object::Int(12), Object::Int(20)]),
+        ),
+        ("x".to_string(), Object::Int(100)),
+    ]));
+    let tests = vec![
+        (
+            "map([items], x => x * 2)",
+            list(vec![
+                Object::Double(10.0),
+                Object::Double(24.0),
+                Object::Double(40.0),
+            ]),
+        ),
+        (
+            "filter([items], x => x > 10)",
+            list(vec![Object::Int(12), Object::Int(20)]),
+        ),
+        (
+            "reduce([items], 0, (acc, x) => acc + x)",
+            Object::Double(37.0),
+        ),
+        // Outside the lambda, x is the parameter again.
+        ("Sum(map(items, (y) => y + [x])) + x", Object::Double(437.0)),
+        ("map({}, x => [missing])", list(vec![])),
+        ("filter(null, x => true)", Object::Null),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, context.clone()).unwrap(),
+            "{test}"
+        );
+    }
+    assert!(matches!(
+        evaluator::eval_input("reduce([items], 0, x => x)", context.clone()),
+        Err(EvalError::MissMatchFunctionArguements {
+            expected: 2,
+            found: 1
+        })
+    ));
+    assert!(matches!(
+        evaluator::eval_input("x => x", context),
+        Err(EvalError::ExpectedExpressionXFoundY { .. })
+    ));
+}
+
+#[test]
+fn evaluate_member_access() {
+    let map = |members: Vec<(&str, Object)>| {
+        Object::Map(Rc::new(HashMap::from_iter(
+            members
+                .into_iter()
+                .map(|(name, object)| (name.to_string(), object)),
+        )))
+    };
+    let address = map(vec![("city", Object::Str(Rc::new("Oslo".to_string())))]);
+    let context = Context::from_map(HashMap::from_iter([
+        (
+            "customer".to_string(),
+            map(vec![("address", address), ("phone", Object::Null)]),
+        ),
+        (
+            "orders".to_string(),
+            Object::List(Rc::new(vec![
+                map(vec![("total", Object::Int(30))]),
+                map(vec![("total", Object::Double(12.5))]),
+            ])),
+        ),
+    ]));
+    let tests = vec![
+        (
+            "[customer].address.city",
+            Object::Str(Rc::new("Oslo".to_string())),
+        ),
+        ("customer.phone.country", Object::Null),
+        ("[customer].address?.zip ?? 0", Object::Int(0)),
+        ("[customer]?.phone?.country", Object::Null),
+        (
+            "[customer]?.address?.city",
+            Object::Str(Rc::new("Oslo".to_string())),
+        ),
+        ("[orders][1].total", Object::Double(12.5)),
+        ("Sum(map([orders], o => o.total))", Object::Double(42.5)),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, context.clone()).unwrap(),
+            "{test}"
+        );
+    }
+
+    assert!(matches!(
+        evaluator::eval_input("[customer].address.zip ?? 0", context.clone()),
+        Err(EvalError::MemberNotFound(path)) if path == "[customer].address.zip"
+    ));
+    assert!(matches!(
+        evaluator::eval_input("[customer].address.city.name", context.clone()),
+        Err(EvalError::ExpectedObjectXFoundY {
+            expected: "map",
+            ..
+        })
+    ));
+    let context = context.with_options(EvalOptions {
+        missing_parameter: MissingParameterPolicy::Null,
+        ..EvalOptions::default()
+    });
+    assert_eq!(
+        Object::Int(0),
+        evaluator::eval_input("[customer].address.zip ?? 0", context).unwrap()
+    );
+}
+
+#[test]
+fn evaluate_method_calls() {
+    let context = Context::from_map(HashMap::from_iter([
+        (
+            "name".to_string(),
+            Object::Str(Rc::new(" Ada ".to_string())),
+        ),
+        (
+            "items".to_string(),
+            Object::List(Rc::new(vec![
+                Object::Int(1),
+                Object::Int(2),
+                Object::Int(3),
+            ])),
+        ),
+        ("nothing".to_string(), Object::Null),
+    ]));
+    let string = |string: &str| Object::Str(Rc::new(string.to_string()));
+    let tests = vec![
+        ("\"abc\".ToUpper()", string("ABC")),
+        ("[name].Length", Object::Int(5)),
+        ("[name].Trim().ToLower()", string("ada")),
+        ("name.Trim().Length == 3", Object::Bool(true)),
+        ("[items].Count() + [items].Length", Object::Double(6.0)),
+        (
+            "[items].filter(x => x > 1).map(x => x * 2).Sum()",
+            Object::Double(10.0),
+        ),
+        ("[nothing].ToUpper()", Object::Null),
+        ("[nothing].Length", Object::Null),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, context.clone()).unwrap(),
+            "{test}"
+        );
+    }
+
+    assert!(matches!(
+        evaluator::eval_input("[name].Shout()", context.clone()),
+        Err(EvalError::UnknownFunction(name)) if name == "Shout"
+    ));
+    assert!(matches!(
+        evaluator::eval_input("[name].Trim(1)", context.clone()),
+        Err(EvalError::MissMatchFunctionArguements {
+            expected: 1,
+            found: 2
+        })
+    ));
+    assert!(matches!(
+        evaluator::eval_input("[items].ToUpper()", context),
+        Err(EvalError::ExpectedObjectXFoundY {
+            expected: "string",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn evaluate_let_expressions() {
+    let context = Context::from_map(HashMap::from_iter([
+        ("price".to_string(), Object::Int(100)),
+        ("discount".to_string(), Object::Double(0.25)),
+        ("tax".to_string(), Object::Double(0.5)),
+        ("net".to_string(), Object::Int(1)),
+    ]));
+    let tests = vec![
+        (
+            "let net = [price] * (1 - [discount]) in net + net * [tax]",
+            Object::Double(112.5),
+        ),
+        ("let a = 2, b = a * a in a + b", Object::Double(6.0)),
+        // The value is evaluated before its name is bound.
+        ("let net = [net] + 1 in net", Object::Double(2.0)),
+        ("(let net = 5 in net) + net", Object::Double(6.0)),
+        ("let v = [price] in v in (1, 100)", Object::Bool(true)),
+        (
+            "map({1, 2}, x => let y = x * 10 in y + x)",
+            Object::List(Rc::new(vec![Object::Double(11.0), Object::Double(22.0)])),
+        ),
+    ]
+    .into_iter();
+    for (test, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(test, context.clone()).unwrap(),
+            "{test}"
+        );
+    }
+    assert!(matches!(
+        evaluator::eval_input("(let a = 1 in a) + a", context),
+        Err(EvalError::ReferenceNotFound(name)) if name == "a"
+    ));
+}
+
+#[test]
+fn evaluate_scripts() {
+    let context = Context::from_map(HashMap::from_iter([
+        ("a".to_string(), Object::Int(60)),
+        ("b".to_string(), Object::Int(50)),
+    ]));
+    let result = evaluator::eval_script_input(
+        "[total] = [a] + [b]; [flag] = [total] > 100; [a] = [a] * 2;",
+        context.clone(),
+    )
+    .unwrap();
+    assert_eq!(Some(Object::Double(110.0)), result.get("total"));
+    assert_eq!(Some(Object::Bool(true)), result.get("flag"));
+    assert_eq!(Some(Object::Double(120.0)), result.get("a"));
+    assert_eq!(Some(Object::Int(50)), result.get("b"));
+    // The context passed in is left as it was.
+    assert_eq!(Some(Object::Int(60)), context.get("a"));
+    assert_eq!(None, context.get("total"));
+
+    assert!(matches!(
+        evaluator::eval_script_input("[x] = 1; [y] = [z]", context),
+        Err(EvalError::ReferenceNotFound(name)) if name == "z"
+    ));
+}
+
+#[test]
+fn evaluate_templates() {
+    let context = Context::from_map(HashMap::from_iter([
+        ("id".to_string(), Object::Int(42)),
+        ("limit".to_string(), Object::Double(1234567.891)),
+        ("name".to_string(), Object::Str(Rc::new("Ann".to_string()))),
+        ("missing".to_string(), Object::Null),
+    ]));
+    let tests = vec![
+        (
+            "$\"Order {[id]} exceeds {[limit]:0.00}\"",
+            "Order 42 exceeds 1234567.89",
+        ),
+        ("$\"{{{[name].ToUpper()}}}: {[id] > 40}\"", "{ANN}: true"),
+        ("$\"[{[missing]}] [{[missing]:F2}]\"", "[] []"),
+        ("$\"{0.5} {2.0} {-3}\"", "0.5 2 -3"),
+        (
+            "$\"{[limit]:F} {[limit]:f1} {[id]:F0}\"",
+            "1234567.89 1234567.9 42",
+        ),
+        (
+            "$\"{[limit]:N} {[limit]:N0} {-1234:n1}\"",
+            "1,234,567.89 1,234,568 -1,234.0",
+        ),
+        ("$\"{[id]:D5} {-7:D3} {[id]:D}\"", "00042 -007 42"),
+        (
+            "$\"{[limit]:#,##0.##} {0.5:#.##} {0:#.##}\"",
+            "1,234,567.89 .5 ",
+        ),
+        (
+            "$\"{1.5:000.000} {-0.001:0.00} {2.999:0}\"",
+            "001.500 0.00 3",
+        ),
+        // Midpoints are rounded away from zero, unlike `format!`.
+        (
+            "$\"{2.5:F0} {-2.5:F0} {0.125:F2} {9.5:0} {-999.5:N0}\"",
+            "3 -3 0.13 10 -1,000",
+        ),
+        // 1.005 is slightly below the midpoint as a double.
+        ("$\"{1.005:F2} {0.5:F0} {1.5:F0}\"", "1.00 1 2"),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert_eq!(
+            Object::Str(Rc::new(expected.to_string())),
+            evaluator::eval_input(input, context.clone()).unwrap(),
+            "{input}"
+        );
+    }
+
+    let tests = vec![
+        ("$\"{[id]:X2}\"", "X2"),
+        ("$\"{[limit]:D2}\"", "D2"),
+        ("$\"{[id]:0.0.0}\"", "0.0.0"),
+        ("$\"{[id]:yyyy-MM-dd}\"", "yyyy-MM-dd"),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert!(
+            matches!(
+                evaluator::eval_input(input, context.clone()),
+                Err(EvalError::InvalidFormatSpecifier { format, .. }) if format == expected
+            ),
+            "{input}"
+        );
+    }
+    assert!(matches!(
+        evaluator::eval_input("$\"{[name]:0.00}\"", context.clone()),
+        Err(EvalError::ExpectedObjectXFoundY {
+            expected: "number",
+            ..
+        })
+    ));
+    assert!(matches!(
+        evaluator::eval_input("$\"{ {1} }\"", context),
+        Err(EvalError::ExpectedObjectXFoundY {
+            expected: "number, string or bool",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn evaluate_decimals() {
+    let decimal = |value: &str| Object::Decimal(Decimal::from_str_exact(value).unwrap());
+    let context = Context::from_map(HashMap::from_iter([
+        ("price".to_string(), decimal("19.99")),
+        (
+            "prices".to_string(),
+            Object::List(Rc::new(vec![
+                decimal("2.5"),
+                Object::Int(1),
+                decimal("0.25"),
+            ])),
+        ),
+    ]));
+    let tests = vec![
+        ("0.1m + 0.2m", decimal("0.3")),
+        ("0.1m + 0.2m == 0.3m", Object::Bool(true)),
+        ("0.1 + 0.2 == 0.3", Object::Bool(false)),
+        ("[price] * 3", decimal("59.97")),
+        ("[price] - 0.99", decimal("19")),
+        ("1m / 4", decimal("0.25")),
+        ("7.5m // 2", decimal("3")),
+        ("-7.5m % 2", decimal("-1.5")),
+        ("1.5m ** 2", decimal("2.25")),
+        ("2m ** -1", decimal("0.5")),
+        ("4m ** 0.5", Object::Double(2.0)),
+        ("-[price]", decimal("-19.99")),
+        ("[price] > 19.98 && 2M < 2.5", Object::Bool(true)),
+        ("[price] + null", Object::Null),
+        ("Sum([prices])", decimal("3.75")),
+        ("Avg([prices])", decimal("1.25")),
+        ("Min([prices])", decimal("0.25")),
+        ("Max([prices])", decimal("2.5")),
+        (
+            "Sort([prices])",
+            Object::List(Rc::new(vec![
+                decimal("0.25"),
+                Object::Int(1),
+                decimal("2.5"),
+            ])),
+        ),
+        ("{10, 20, 30}[1.0m]", Object::Int(20)),
+        (
+            "$\"{1.005m:F2} {[price]:N1} {-2.5m:0} {[price]}\"",
+            Object::Str(Rc::new("1.01 20.0 -3 19.99".to_string())),
+        ),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(input, context.clone()).unwrap(),
+            "{input}"
+        );
+    }
+
+    assert!(matches!(
+        evaluator::eval_input("1m / 0", context.clone()),
+        Err(EvalError::DivideByZero)
+    ));
+    assert!(matches!(
+        evaluator::eval_input("79228162514264337593543950335m * 2", context),
+        Err(EvalError::ArithmeticOverflow {
+            operator: Token::Asterisk,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn evaluate_rounding_functions() {
+    let decimal = |value: &str| Object::Decimal(Decimal::from_str_exact(value).unwrap());
+    let tests = vec![
+        ("Round(2.5)", Object::Double(2.0)),
+        ("Round(3.5)", Object::Double(4.0)),
+        ("Round(-2.5)", Object::Double(-2.0)),
+        ("Round(1.2345, 2)", Object::Double(1.23)),
+        ("Round(1.5, 20)", Object::Double(1.5)),
+        ("Floor(-1.5)", Object::Double(-2.0)),
+        ("Ceiling(1.2)", Object::Double(2.0)),
+        ("Truncate(-1.7)", Object::Double(-1.0)),
+        ("Round(7)", Object::Int(7)),
+        ("Ceiling(7 // 2)", Object::Int(3)),
+        ("Round(null)", Object::Null),
+        ("Round(1.5, null)", Object::Null),
+        ("Round(2.345m, 2)", decimal("2.34")),
+        ("Round(2.355m, 2)", decimal("2.36")),
+        ("Round(-2.5m)", decimal("-2")),
+        ("Round(1.25m, 30)", decimal("1.25")),
+        ("Floor(-1.05m)", decimal("-2")),
+        ("Ceiling(1.01m)", decimal("2")),
+        ("Truncate(-1.99m)", decimal("-1")),
+        ("(0.1m + 0.2m).Round(1) == 0.3m", Object::Bool(true)),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(input, Context::default()).unwrap(),
+            "{input}"
+        );
+    }
+
+    assert!(matches!(
+        evaluator::eval_input("Round(\"a\")", Context::default()),
+        Err(EvalError::ExpectedObjectXFoundY {
+            expected: "number",
+            ..
+        })
+    ));
+    assert!(matches!(
+        evaluator::eval_input("Round(1.5, -1)", Context::default()),
+        Err(EvalError::ExpectedObjectXFoundY {
+            expected: "non-negative int",
+            ..
+        })
+    ));
+    assert!(matches!(
+        evaluator::eval_input("Floor(1.5, 1)", Context::default()),
+        Err(EvalError::MissMatchFunctionArguements {
+            expected: 1,
+            found: 2
+        })
+    ));
+}
+
+#[test]
+fn decimal_as_default_option() {
+    let context = Context::default().with_options(EvalOptions {
+        decimal_as_default: true,
+        ..EvalOptions::default()
+    });
+    let tests = vec![
+        ("0.1 + 0.2 == 0.3", Object::Bool(true)),
+        (
+            "0.1 + 0.2",
+            Object::Decimal(Decimal::from_str_exact("0.3").unwrap()),
+        ),
+        ("1 + 2", Object::Double(3.0)),
+        ("7 // 2", Object::Int(3)),
+        ("$\"{0.1 + 0.2}\"", Object::Str(Rc::new("0.3".to_string()))),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(input, context.clone()).unwrap(),
+            "{input}"
+        );
+    }
+    let context = evaluator::eval_script_input("[total] = 0.1 + 0.2;", context).unwrap();
+    assert_eq!(
+        Some(Object::Decimal(Decimal::from_str_exact("0.3").unwrap())),
+        context.get("total")
+    );
+}
+
+#[test]
+fn caret_power_option() {
+    let context = Context::default().with_options(EvalOptions {
+        caret_power: true,
+        ..EvalOptions::default()
+    });
+    assert_eq!(
+        Object::Int(512),
+        evaluator::eval_input("2 ^ 3 ^ 2", context.clone()).unwrap()
+    );
+    let context = evaluator::eval_script_input("[area] = 3 ^ 2 * 2", context).unwrap();
+    assert_eq!(Some(Object::Double(18.0)), context.get("area"));
+    assert_ne!(
+        Some(Object::Int(8)),
+        evaluator::eval_input("2 ^ 3", Context::default()).ok()
+    );
+}
+
+#[test]
+fn evaluate_big_integers() {
+    let big = |value: &str| Object::BigInt(Rc::new(value.parse::<BigInt>().unwrap()));
+    let context = Context::from_map(HashMap::from_iter([(
+        "ids".to_string(),
+        Object::List(Rc::new(vec![
+            Object::Int(7),
+            big("18446744073709551616"),
+            big("-18446744073709551616"),
+        ])),
+    )]));
+    let tests = vec![
+        ("9223372036854775808", big("9223372036854775808")),
+        ("-9223372036854775808", Object::Int(i64::MIN)),
+        ("9223372036854775808 - 1", Object::Int(i64::MAX)),
+        ("100000000000000000000 * 3", big("300000000000000000000")),
+        ("-100000000000000000000 // 7", big("-14285714285714285714")),
+        ("-100000000000000000000 % 7", Object::Int(-2)),
+        (
+            "100000000000000000000 / 8",
+            Object::Double(12500000000000000000.0),
+        ),
+        (
+            "100000000000000000000 ** 2",
+            big("10000000000000000000000000000000000000000"),
+        ),
+        ("100000000000000000000 ** -1", Object::Double(1e-20)),
+        ("-1 ** 10000000000000000000", Object::Int(1)),
+        ("100000000000000000000 + 0.5", Object::Double(1e20)),
+        (
+            "100000000000000000000 + 0.5m",
+            Object::Decimal(Decimal::from_str_exact("100000000000000000000.5").unwrap()),
+        ),
+        (
+            "100000000000000000000 > 9223372036854775807",
+            Object::Bool(true),
+        ),
+        (
+            "9223372036854775809 == 9223372036854775808",
+            Object::Bool(false),
+        ),
+        (
+            "100000000000000000000 == 100000000000000000000.0",
+            Object::Bool(true),
+        ),
+        ("-[ids][1] in [ids]", Object::Bool(true)),
+        ("Max([ids])", big("18446744073709551616")),
+        (
+            "Sort([ids])",
+            Object::List(Rc::new(vec![
+                big("-18446744073709551616"),
+                Object::Int(7),
+                big("18446744073709551616"),
+            ])),
+        ),
+        (
+            "$\"{[ids][1]} {[ids][2]:N0} {[ids][1]:D22} {-[ids][0]:D3} {[ids][1]:F1}\"",
+            Object::Str(Rc::new(
+                "18446744073709551616 -18,446,744,073,709,551,616 0018446744073709551616 -007 \
+                 18446744073709551616.0"
+                    .to_string(),
+            )),
+        ),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert_eq!(
+            expected,
+            evaluator::eval_input(input, context.clone()).unwrap(),
+            "{input}"
+        );
+    }
+
+    assert!(matches!(
+        evaluator::eval_input("100000000000000000000 // 0", context.clone()),
+        Err(EvalError::DivideByZero)
+    ));
+    assert!(matches!(
+        evaluator::eval_input("100000000000000000000 ** 100000", context.clone()),
+        Err(EvalError::ArithmeticOverflow {
+            operator: Token::DoubleAsterisk,
+            ..
+        })
+    ));
+    assert!(matches!(
+        evaluator::eval_input("{1}[100000000000000000000]", context),
+        Err(EvalError::IndexOutOfBounds {
+            index: i64::MAX,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn promote_to_big_int_option() {
+    let big = |value: &str| Object::BigInt(Rc::new(value.parse::<BigInt>().unwrap()));
+    let tests = vec![
+        ("1 + 2 * 3", Object::Int(7), Object::Double(7.0)),
+        ("7 / 2", Object::Double(3.5), Object::Double(3.5)),
+        (
+            "9223372036854775807 + 1",
+            big("9223372036854775808"),
+            Object::Double(9223372036854775808.0),
+        ),
+        (
+            "9223372036854775807 + 1 - 1",
+            Object::Int(i64::MAX),
+            Object::Double(9223372036854775808.0),
+        ),
+        (
+            "9223372036854775807 + 1 > 9223372036854775807",
+            Object::Bool(true),
+            Object::Bool(false),
+        ),
+        (
+            "3037000500 * -3037000500",
+            big("-9223372037000250000"),
+            Object::Double(-9223372037000250000.0),
+        ),
+        (
+            "2 ** 64",
+            big("18446744073709551616"),
+            Object::Double(18446744073709551616.0),
+        ),
+        ("2 ** -1", Object::Double(0.5), Object::Double(0.5)),
+        (
+            "-(-9223372036854775807 - 1)",
+            big("9223372036854775808"),
+            Object::Double(9223372036854775808.0),
+        ),
+        (
+            "(-9223372036854775807 - 1) // -1",
+            big("9223372036854775808"),
+            Object::Double(9223372036854775808.0),
+        ),
+        ("Sum({1, 2})", Object::Int(3), Object::Double(3.0)),
+        (
+            "Sum({9223372036854775807, 1})",
+            big("9223372036854775808"),
+            Object::Double(9223372036854775808.0),
+        ),
+        ("Avg({1, 2})", Object::Double(1.5), Object::Double(1.5)),
+    ]
+    .into_iter();
+    let promoting = Context::default().with_options(EvalOptions {
+        promote_to_big_int: true,
+        ..EvalOptions::default()
+    });
+    for (input, promoted, default) in tests {
+        assert_eq!(
+            promoted,
+            evaluator::eval_input(input, promoting.clone()).unwrap(),
+            "{input}"
+        );
+        assert_eq!(
+            default,
+            evaluator::eval_input(input, Context::default()).unwrap(),
+            "{input}"
+        );
+    }
+    assert!(matches!(
+        evaluator::eval_input("2 ** 10000000", promoting),
+        Err(EvalError::ArithmeticOverflow { .. })
+    ));
+}
+
+#[test]
+fn integers_beyond_doubles_compare_exactly() {
+    let tests = vec![
+        ("9007199254740993 == 9007199254740992", Object::Bool(false)),
+        ("9007199254740993 <> 9007199254740992", Object::Bool(true)),
+        ("9007199254740993 > 9007199254740992", Object::Bool(true)),
+        (
+            "9007199254740993 in (9007199254740992)",
+            Object::Bool(false),
+        ),
+        (
+            "9007199254740993 not in {9007199254740992, 1}",
+            Object::Bool(true),
+        ),
+        (
+            "{9007199254740993} == {9007199254740992}",
+            Object::Bool(false),
+        ),
+        (
+            "Count(Distinct({9007199254740993, 9007199254740992}))",
+            Object::Int(2),
+        ),
+        (
+            "Max({9007199254740992, 9007199254740993})",
+            Object::Int(9007199254740993),
+        ),
+        (
+            "Sort({9007199254740993, 9007199254740992})[0]",
+            Object::Int(9007199254740992),
+        ),
+    ]
+    .into_iter();
+    let promoting = Context::default().with_options(EvalOptions {
+        promote_to_big_int: true,
+        ..EvalOptions::default()
+    });
+    for (input, expected) in tests {
+        for context in [Context::default(), promoting.clone()] {
+            assert_eq!(
+                expected,
+                evaluator::eval_input(input, context).unwrap(),
+                "{input}"
+            );
+        }
+    }
+}
@@ -1,6 +1,16 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
-use crate::{expression::Expression, lexer::Lexer, parser::Parser, token::Token};
+use crate::{
+    expression::{Assignment, Expression, ParseExpressionError, Span, SpanTree, TemplatePart},
+    lexer::Lexer,
+    object::EvalOptions,
+    parser::{
+        parse_input, parse_input_with, parse_input_with_spans, parse_script, parse_script_with,
+        Parser,
+    },
+    rewrite::{substitute, Substitution},
+    token::Token,
+};
 
 #[test]
 fn test_basic_expressions() {
@@ -35,6 +45,77 @@ fn test_basic_expressions() {
     }
 }
 
+#[test]
+fn test_logical_operator_precedence() {
+    let infix = |operator, left, right| Expression::InfixExpression {
+        operator,
+        left: Box::new(left),
+        right: Box::new(right),
+    };
+    let ident = |name: &str| Expression::Ident(Rc::new(name.to_string()));
+    let tests = vec![
+        (
+            "[a] or [b] and [c]",
+            infix(
+                Token::Or,
+                ident("a"),
+                infix(Token::And, ident("b"), ident("c")),
+            ),
+        ),
+        (
+            "[a] && [b] || [c]",
+            infix(
+                Token::DoublePipe,
+                infix(Token::DoubleAmpersand, ident("a"), ident("b")),
+                ident("c"),
+            ),
+        ),
+        (
+            "1 == 1 and [a]",
+            infix(
+                Token::And,
+                infix(Token::Equals, Expression::Int(1), Expression::Int(1)),
+                ident("a"),
+            ),
+        ),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        let mut parser = Parser::new(Lexer::from_input(input)).unwrap();
+        assert_eq!(expected, parser.parse().unwrap(), "{input}");
+    }
+}
+
+#[test]
+fn test_prefix_and_remainder_precedence() {
+    let tests = vec![
+        ("not [a] or [b]", "(not [a]) or [b]"),
+        ("![a] && [b]", "(![a]) && [b]"),
+        ("-2 * 3", "(-2) * 3"),
+        ("-[a] + 1", "(-[a]) + 1"),
+        ("7 % 4 * 2", "(7 % 4) * 2"),
+        ("1 + 7 % 4", "1 + (7 % 4)"),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert_eq!(
+            parse_input(expected).unwrap(),
+            parse_input(input).unwrap(),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn test_parse_input() {
+    let mut parser = Parser::new(Lexer::from_input("[a] * (1 + 2)")).unwrap();
+    assert_eq!(
+        parser.parse().unwrap(),
+        parse_input("[a] * (1 + 2)").unwrap()
+    );
+    assert!(parse_input("[a] *").is_err());
+}
+
 #[test]
 fn test_basic_infix_expressions() {
     let tests = vec![
@@ -112,6 +193,17 @@ fn test_basic_infix_expressions() {
                 arguments: vec![Box::new(Expression::Int(1)), Box::new(Expression::Int(2))],
             },
         ),
+        (
+            "add(1, 2) * 3",
+            Expression::InfixExpression {
+                operator: Token::Asterisk,
+                left: Box::new(Expression::CallExpression {
+                    function: Box::new(Expression::Ident(Rc::new("add".to_string()))),
+                    arguments: vec![Box::new(Expression::Int(1)), Box::new(Expression::Int(2))],
+                }),
+                right: Box::new(Expression::Int(3)),
+            },
+        ),
         (
             "add(-1, 2)",
             Expression::CallExpression {
@@ -133,6 +225,7 @@ fn test_basic_infix_expressions() {
             },
         ),
         ("[x]", Expression::Ident(Rc::new("x".to_string()))),
+        ("x", Expression::BareIdent(Rc::new("x".to_string()))),
         ("[x_]", Expression::Ident(Rc::new("x_".to_string()))),
         ("[x1212_]", Expression::Ident(Rc::new("x1212_".to_string()))),
     ]
@@ -144,3 +237,280 @@ fn test_basic_infix_expressions() {
         assert_eq!(expected, expression);
     }
 }
+
+#[test]
+fn test_exponent_expressions() {
+    let tests = vec![
+        ("2 ** 3 ** 2", "2 ** (3 ** 2)"),
+        ("2 * 3 ** 2", "2 * (3 ** 2)"),
+        ("2 ** 3 * 2", "(2 ** 3) * 2"),
+        ("-2 ** 2", "(-2) ** 2"),
+        ("2 ** -1 ** 2", "2 ** ((-1) ** 2)"),
+        (
+            "[a] ** [b].Length ** f(1)[0]",
+            "[a] ** (([b].Length) ** (f(1)[0]))",
+        ),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert_eq!(
+            parse_input(expected).unwrap(),
+            parse_input(input).unwrap(),
+            "{input}"
+        );
+    }
+    assert_ne!(parse_input("2 ** 3").ok(), parse_input("2 ^ 3").ok());
+    let options = EvalOptions {
+        caret_power: true,
+        ..EvalOptions::default()
+    };
+    let parse_with_caret_power = |input| parse_input_with(input, &options).unwrap();
+    assert_eq!(
+        parse_input("2 ** 3 ** 2").unwrap(),
+        parse_with_caret_power("2 ^ 3 ^ 2")
+    );
+    assert_eq!(
+        parse_input("$\"{2 ** -[x]}\"").unwrap(),
+        parse_with_caret_power("$\"{2 ^ -[x]}\"")
+    );
+    assert_eq!(
+        parse_script("[y] = 2 ** [x]").unwrap(),
+        parse_script_with("[y] = 2 ^ [x]", &options).unwrap()
+    );
+}
+
+#[test]
+fn test_decimal_literals_option() {
+    let options = EvalOptions {
+        decimal_as_default: true,
+        ..EvalOptions::default()
+    };
+    assert_eq!(
+        parse_input("[a] + 0.1m * 2").unwrap(),
+        parse_input_with("[a] + 0.1 * 2", &options).unwrap()
+    );
+    assert_eq!(
+        parse_script("[a] = $\"{0.5m}\"").unwrap(),
+        parse_script_with("[a] = $\"{0.5}\"", &options).unwrap()
+    );
+}
+
+#[test]
+fn test_spans() {
+    let (_, spans) = parse_input_with_spans("f(2, \"a\") * -(1 + [x])").unwrap();
+    let leaf = |start, end| SpanTree {
+        span: Span { start, end },
+        children: vec![],
+    };
+    let node = |start, end, children| SpanTree {
+        span: Span { start, end },
+        children,
+    };
+    assert_eq!(
+        node(
+            0,
+            22,
+            vec![
+                node(0, 9, vec![leaf(0, 1), leaf(2, 3), leaf(5, 8)]),
+                node(12, 22, vec![node(13, 22, vec![leaf(14, 15), leaf(18, 21)])]),
+            ]
+        ),
+        spans
+    );
+}
+
+#[test]
+fn test_in_spans() {
+    let leaf = |start, end| SpanTree {
+        span: Span { start, end },
+        children: vec![],
+    };
+    let node = |start, end, children| SpanTree {
+        span: Span { start, end },
+        children,
+    };
+
+    let (_, spans) = parse_input_with_spans("x not in (1, 2)").unwrap();
+    assert_eq!(
+        node(
+            0,
+            15,
+            vec![leaf(0, 1), node(9, 15, vec![leaf(10, 11), leaf(13, 14)])]
+        ),
+        spans
+    );
+
+    // The list of the function form spans its elements.
+    let (_, spans) = parse_input_with_spans("in(x, 1, 2)").unwrap();
+    assert_eq!(
+        node(
+            0,
+            11,
+            vec![leaf(3, 4), node(6, 11, vec![leaf(6, 7), leaf(9, 10)])]
+        ),
+        spans
+    );
+
+    let (_, spans) = parse_input_with_spans("x in ((y))").unwrap();
+    assert_eq!(node(0, 10, vec![leaf(0, 1), leaf(5, 10)]), spans);
+}
+
+#[test]
+fn test_doubly_parenthesized_in_operand() {
+    // Each input parses like `expected` with `[c]` replaced by `operand`.
+    let tests = vec![
+        ("[x] in (([a] ?? [b]))", "[x] in [c]", "[a] ?? [b]"),
+        ("[x] not in ((([l])))", "[x] not in [c]", "[l]"),
+        ("[x] in ((1) + 1)", "[x] in ([c])", "1 + 1"),
+        ("[x] in ((1), 2)", "[x] in ([c], 2)", "1"),
+        ("[x] in ((f)(1))", "[x] in ([c])", "(f)(1)"),
+    ]
+    .into_iter();
+    for (input, expected, operand) in tests {
+        let substitutions = HashMap::from_iter([(
+            "c".to_string(),
+            Substitution::Replace(parse_input(operand).unwrap()),
+        )]);
+        let expected = substitute(parse_input(expected).unwrap(), &substitutions).unwrap();
+        assert_eq!(expected, parse_input(input).unwrap(), "{input}");
+    }
+}
+
+#[test]
+fn test_let_expressions() {
+    let name = |name: &str| Rc::new(name.to_string());
+    let bare = |ident: &str| Box::new(Expression::BareIdent(name(ident)));
+    // The first `in` after a value ends it, unless it is within brackets.
+    let (expression, spans) =
+        parse_input_with_spans("let a = (1 in {1}), b = a in b in {b}").unwrap();
+    assert_eq!(
+        Expression::Let {
+            name: name("a"),
+            value: Box::new(Expression::InfixExpression {
+                operator: Token::In,
+                left: Box::new(Expression::Int(1)),
+                right: Box::new(Expression::List(vec![Box::new(Expression::Int(1))])),
+            }),
+            body: Box::new(Expression::Let {
+                name: name("b"),
+                value: bare("a"),
+                body: Box::new(Expression::InfixExpression {
+                    operator: Token::In,
+                    left: bare("b"),
+                    right: Box::new(Expression::List(vec![bare("b")])),
+                }),
+            }),
+        },
+        expression
+    );
+    assert_eq!(Span { start: 0, end: 37 }, spans.span);
+    assert_eq!(Span { start: 8, end: 18 }, spans.children[0].span);
+    assert_eq!(Span { start: 20, end: 37 }, spans.children[1].span);
+
+    assert!(matches!(
+        parse_input("let x in x"),
+        Err(ParseExpressionError::ExpectedXFoundY {
+            expected: "Token::Assign",
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse_input("let x = 1 + 2"),
+        Err(ParseExpressionError::ExpectedXFoundY {
+            expected: "Token::In",
+            found: Token::EOF,
+        })
+    ));
+    assert!(matches!(
+        parse_input("let 1 = 1 in 1"),
+        Err(ParseExpressionError::ExpectedXFoundY {
+            expected: "Identifier",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_templates() {
+    let (expression, spans) =
+        parse_input_with_spans("$\"Order {[id]} over {[limit] * 2:0.00}\"").unwrap();
+    let text = |text: &str| TemplatePart::Text(Rc::new(text.to_string()));
+    assert_eq!(
+        Expression::Template(vec![
+            text("Order "),
+            TemplatePart::Value {
+                expression: Box::new(parse_input("[id]").unwrap()),
+                format: None,
+            },
+            text(" over "),
+            TemplatePart::Value {
+                expression: Box::new(parse_input("[limit] * 2").unwrap()),
+                format: Some(Rc::new("0.00".to_string())),
+            },
+        ]),
+        expression
+    );
+    let leaf = |start, end| SpanTree {
+        span: Span { start, end },
+        children: vec![],
+    };
+    assert_eq!(
+        SpanTree {
+            span: Span { start: 0, end: 39 },
+            children: vec![
+                leaf(9, 13),
+                SpanTree {
+                    span: Span { start: 21, end: 32 },
+                    children: vec![leaf(21, 28), leaf(31, 32)],
+                },
+            ],
+        },
+        spans
+    );
+
+    let tests = vec!["$\"{}\"", "$\"{1 2}\"", "$\"{(1}\""].into_iter();
+    for input in tests {
+        assert!(parse_input(input).is_err(), "{input}");
+    }
+}
+
+#[test]
+fn test_scripts() {
+    let script = parse_script("[total] = [a] + 1; flag = let t = [total] in t > 2;").unwrap();
+    assert_eq!(
+        vec![
+            Assignment {
+                target: Rc::new("total".to_string()),
+                value: parse_input("[a] + 1").unwrap(),
+            },
+            Assignment {
+                target: Rc::new("flag".to_string()),
+                value: parse_input("let t = [total] in t > 2").unwrap(),
+            },
+        ],
+        script.statements
+    );
+    assert_eq!(1, parse_script("x = 1").unwrap().statements.len());
+
+    let tests = vec![
+        ("[x] = 1 [y] = 2", "Token::Semicolon"),
+        ("[x] == 1", "Token::Assign"),
+        ("1 = 2", "Identifier"),
+    ]
+    .into_iter();
+    for (input, expected_token) in tests {
+        assert!(
+            matches!(
+                parse_script(input),
+                Err(ParseExpressionError::ExpectedXFoundY { expected, .. }) if expected == expected_token
+            ),
+            "{input}"
+        );
+    }
+    assert!(matches!(
+        parse_script("[x] = 1;;"),
+        Err(ParseExpressionError::UnexpectedToken {
+            token: Token::Semicolon
+        })
+    ));
+}
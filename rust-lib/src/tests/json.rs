@@ -0,0 +1,119 @@
+use std::rc::Rc;
+
+use num_bigint::BigInt;
+
+use crate::{
+    expression::Expression,
+    json::{expression_from_json, expression_to_json, object_from_json, object_to_json, JsonError},
+    object::Object,
+    parser::parse_input,
+    token::Token,
+};
+
+#[test]
+fn expressions_round_trip_through_json_and_source() {
+    let tests = vec![
+        "1 + 2 * [x]",
+        "if([a] and not [b], max(-1, 2.5), \"s\")",
+        "x <> 3 || [y] % 2 == 0",
+        "[customer].name.Trim().ToUpper() == \"A\"",
+        "[customer]?.address.city ?? 1?.x",
+    ]
+    .into_iter();
+
+    for input in tests {
+        let expression = parse_input(input).unwrap();
+        let json = expression_to_json(&expression).unwrap();
+        let from_json = expression_from_json(&json).unwrap();
+        assert_eq!(expression, from_json, "{json}");
+        assert_eq!(
+            expression,
+            parse_input(&from_json.to_string()).unwrap(),
+            "{json}"
+        );
+    }
+}
+
+#[test]
+fn json_layout() {
+    let expression = parse_input("-[x] + f(1)").unwrap();
+    assert_eq!(
+        r#"{"InfixExpression":{"operator":"Plus","left":{"PrefixExpression":{"operator":"Minus","expression":{"Ident":"x"}}},"right":{"CallExpression":{"function":{"Ident":"f"},"arguments":[{"Int":1}]}}}}"#,
+        expression_to_json(&expression).unwrap()
+    );
+}
+
+#[test]
+fn rejects_trees_the_parser_cannot_produce() {
+    let infix = |operator: Token| Expression::InfixExpression {
+        operator,
+        left: Box::new(Expression::Int(1)),
+        right: Box::new(Expression::Int(2)),
+    };
+    let tests = vec![
+        infix(Token::LParen),
+        infix(Token::Bang),
+        Expression::PrefixExpression {
+            operator: Token::Plus,
+            expression: Box::new(Expression::Int(1)),
+        },
+        Expression::Ident(Rc::new("two words".to_string())),
+        Expression::BareIdent(Rc::new("and".to_string())),
+        Expression::CallExpression {
+            function: Box::new(Expression::Int(1)),
+            arguments: vec![],
+        },
+        Expression::Int(-1),
+        Expression::BigInt(Rc::new(BigInt::from(i64::MAX))),
+        Expression::Nil,
+    ]
+    .into_iter();
+
+    for expression in tests {
+        let json = expression_to_json(&expression).unwrap();
+        assert!(expression_from_json(&json).is_err(), "{json}");
+    }
+
+    let small_big_int = Expression::BigInt(Rc::new(BigInt::from(5)));
+    assert!(matches!(
+        expression_from_json(&expression_to_json(&small_big_int).unwrap()),
+        Err(JsonError::InvalidBigInt { .. })
+    ));
+    assert!(matches!(
+        expression_from_json(r#"{"Int": "1"}"#),
+        Err(JsonError::Malformed(_))
+    ));
+}
+
+#[test]
+fn objects_round_trip_through_json() {
+    let tests = vec![
+        Object::Int(-3),
+        Object::Double(0.25),
+        Object::Bool(true),
+        Object::Str(Rc::new("é\"".to_string())),
+    ]
+    .into_iter();
+
+    for object in tests {
+        let json = object_to_json(&object).unwrap();
+        assert_eq!(object, object_from_json(&json).unwrap());
+    }
+}
+
+#[test]
+fn non_finite_doubles_round_trip_through_json() {
+    let json = object_to_json(&Object::Double(f64::NAN)).unwrap();
+    assert_eq!(r#"{"Double":"NaN"}"#, json);
+    assert!(matches!(object_from_json(&json), Ok(Object::Double(double)) if double.is_nan()));
+
+    for double in [f64::INFINITY, f64::NEG_INFINITY] {
+        let object = Object::List(Rc::new(vec![Object::Double(double)]));
+        let json = object_to_json(&object).unwrap();
+        assert_eq!(object, object_from_json(&json).unwrap(), "{json}");
+    }
+    assert!(matches!(
+        object_from_json(r#"{"Double":"nan"}"#),
+        Err(JsonError::Malformed(_))
+    ));
+}
@@ -1,12 +1,28 @@
 #![cfg(test)]
+#![allow(clippy::unwrap_used)]
 
+mod analysis;
+mod binary;
 mod evaluator;
 mod expression;
+#[cfg(feature = "json")]
+mod json;
+mod optimizer;
+mod printer;
+mod rewrite;
+mod typeck;
+mod visitor;
 
 mod lexer {
     use std::rc::Rc;
 
-    use crate::{lexer::Lexer, token::Token};
+    use num_bigint::BigInt;
+    use rust_decimal::Decimal;
+
+    use crate::{
+        lexer::Lexer,
+        token::{TemplateToken, Token},
+    };
 
     #[test]
     fn test_tokens() {
@@ -38,12 +54,38 @@ mod lexer {
         assert_eq!(None, lexer.next());
     }
 
+    #[test]
+    fn test_malformed_number_tokens() {
+        let mut lexer = Lexer::from_input("1.2.3 4");
+        assert_eq!(Token::Illegal, lexer.next().unwrap());
+        assert_eq!(Token::IntLiteral(4), lexer.next().unwrap());
+    }
+
+    #[test]
+    fn test_malformed_string_tokens() {
+        let tests = vec!["\"\\q\"", "\"a\\", "$\"\\q\""].into_iter();
+        for input in tests {
+            assert_eq!(
+                Token::Illegal,
+                Lexer::from_input(input).next().unwrap(),
+                "{input}"
+            );
+        }
+    }
+
     #[test]
     fn test_number_tokens() {
         let tests = vec![
             ("123.4", Token::DoubleLiteral(123.4)),
             ("1", Token::IntLiteral(1)),
             ("100", Token::IntLiteral(100)),
+            ("1.50m", Token::DecimalLiteral(Decimal::new(150, 2))),
+            ("2M", Token::DecimalLiteral(Decimal::new(2, 0))),
+            ("9223372036854775807", Token::IntLiteral(i64::MAX)),
+            (
+                "9223372036854775808",
+                Token::BigIntLiteral(Rc::new(BigInt::from(i64::MAX) + 1)),
+            ),
         ]
         .into_iter();
 
@@ -51,6 +93,127 @@ mod lexer {
             let mut lexer = Lexer::from_input(input);
             assert_eq!(expected, lexer.next().unwrap());
         }
+
+        let mut lexer = Lexer::from_input("0.1 2 3.0m").with_decimal_literals(true);
+        assert_eq!(
+            Token::DecimalLiteral(Decimal::new(1, 1)),
+            lexer.next().unwrap()
+        );
+        assert_eq!(Token::IntLiteral(2), lexer.next().unwrap());
+        assert_eq!(
+            Token::DecimalLiteral(Decimal::new(30, 1)),
+            lexer.next().unwrap()
+        );
+        assert_eq!(
+            Token::Illegal,
+            Lexer::from_input("0.1234567890123456789012345678901m")
+                .next()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_not_in_tokens() {
+        let tests = vec![
+            ("not in", vec![Token::NotIn]),
+            ("not  in(", vec![Token::NotIn, Token::LParen]),
+            (
+                "not inside",
+                vec![Token::Not, Token::Ident(Rc::new("inside".to_string()))],
+            ),
+            ("not (", vec![Token::Not, Token::LParen]),
+            ("not", vec![Token::Not]),
+        ]
+        .into_iter();
+
+        for (input, expected) in tests {
+            let tokens: Vec<Token> = Lexer::from_input(input)
+                .take_while(|token| *token != Token::EOF)
+                .collect();
+            assert_eq!(expected, tokens, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_member_tokens() {
+        let ident = |name: &str| Token::Ident(Rc::new(name.to_string()));
+        let tokens: Vec<Token> = Lexer::from_input("[a].b . c?.d ?? e")
+            .take_while(|token| *token != Token::EOF)
+            .collect();
+        assert_eq!(
+            vec![
+                Token::LBracket,
+                ident("a"),
+                Token::RBracket,
+                Token::Dot,
+                ident("b"),
+                Token::Dot,
+                ident("c"),
+                Token::QuestionDot,
+                ident("d"),
+                Token::DoubleQuestionMark,
+                ident("e"),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_assign_tokens() {
+        let tests = vec![
+            ("=", vec![Token::Assign]),
+            ("= =", vec![Token::Assign, Token::Assign]),
+            ("===", vec![Token::Equals, Token::Assign]),
+            ("=>=", vec![Token::Arrow, Token::Assign]),
+            ("let", vec![Token::Let]),
+            ("***", vec![Token::DoubleAsterisk, Token::Asterisk]),
+            ("* *", vec![Token::Asterisk, Token::Asterisk]),
+            ("///", vec![Token::DoubleSlash, Token::Slash]),
+        ]
+        .into_iter();
+
+        for (input, expected) in tests {
+            let tokens: Vec<Token> = Lexer::from_input(input)
+                .take_while(|token| *token != Token::EOF)
+                .collect();
+            assert_eq!(expected, tokens, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_template_tokens() {
+        let hole = |source: &str, offset, format: Option<&str>| TemplateToken::Hole {
+            source: Rc::new(source.to_string()),
+            offset,
+            format: format.map(|format| Rc::new(format.to_string())),
+        };
+        let text = |text: &str| TemplateToken::Text(Rc::new(text.to_string()));
+        let tests = vec![
+            (
+                "$\"a{{ {[x]:0.00} }}\\n\"",
+                Token::Template(Rc::new(vec![
+                    text("a{ "),
+                    hole("[x]", 7, Some("0.00")),
+                    text(" }\n"),
+                ])),
+            ),
+            (
+                "$\"{f(\"}\", {1}[0])}\"",
+                Token::Template(Rc::new(vec![hole("f(\"}\", {1}[0])", 3, None)])),
+            ),
+            ("$\"}\"", Token::Illegal),
+            ("$\"{[x]\"", Token::Illegal),
+            ("$", Token::Illegal),
+        ]
+        .into_iter();
+
+        for (input, expected) in tests {
+            assert_eq!(
+                expected,
+                Lexer::from_input(input).next().unwrap(),
+                "{input}"
+            );
+        }
     }
 
     #[test]
@@ -0,0 +1,190 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    evaluator,
+    object::{Context, EvalOptions, Object},
+    optimizer::{optimize, partial_eval},
+    parser::parse_input,
+};
+
+#[test]
+fn folds_constants_and_identities() {
+    let tests = vec![
+        ("(2 * 3) + [x] * 1 + 0", "6.0 + [x] * 1 + 0"),
+        ("1 + 2 < 4", "true"),
+        ("-(2 - 5)", "3.0"),
+        ("\"a\"", "\"a\""),
+        ("true && [x] > 1", "[x] > 1"),
+        ("[x] == 2 and true", "[x] == 2"),
+        ("false || not [x]", "not [x]"),
+        ("[x] in {1, 2} or false", "[x] in {1, 2}"),
+        ("false and [x]", "false"),
+        ("true or [x]", "true"),
+        ("if(1 < 2, [x], [y])", "[x]"),
+        ("if(false, [x], [y] * 1)", "[y] * 1"),
+        ("if([c], 1 + 1, [y])", "if([c], 2.0, [y])"),
+        ("null ?? [x] * 1", "[x] * 1"),
+        ("1 ?? [x]", "1"),
+        ("if(null, [x], [y])", "[y]"),
+        ("$\"{1 + 1:F1} {null}{true}\"", "\"2.0 true\""),
+        ("$\"{[x]} {1 + 1}\"", "$\"{[x]} {2.0}\""),
+        ("0.1m + 0.2m * [x] * 1 + 0", "0.1m + 0.2m * [x] * 1 + 0"),
+        ("0.1m + 0.2m - 1", "-0.7m"),
+        ("100000000000000000000 // 3", "33333333333333333333"),
+        ("-(100000000000000000000 - 1)", "-99999999999999999999"),
+        ("9223372036854775808 - 1", "9223372036854775807"),
+        ("-9223372036854775808 // 1", "-9223372036854775808"),
+    ]
+    .into_iter();
+
+    for (input, expected) in tests {
+        let expression = parse_input(input).unwrap();
+        assert_eq!(
+            parse_input(expected).unwrap(),
+            optimize(&expression),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn keeps_identities_on_operands_of_unknown_type() {
+    let tests = vec![
+        ("[x] * 1", Object::Int(2)),
+        ("0 + [x] - 0", Object::Str(Rc::new("a".to_string()))),
+        ("true && [x]", Object::Int(2)),
+        ("[x] or false", Object::Str(Rc::new("a".to_string()))),
+    ]
+    .into_iter();
+
+    for (input, x) in tests {
+        let expression = parse_input(input).unwrap();
+        let optimized = optimize(&expression);
+        assert_eq!(expression, optimized, "{input}");
+        // Rewriting to `[x]` would give `x` instead of the original result.
+        let context = Context::from_map(HashMap::from_iter([("x".to_string(), x.clone())]));
+        assert_ne!(
+            Some(x),
+            evaluator::eval(&expression, context).ok(),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn keeps_expressions_that_fail_at_runtime() {
+    let tests = vec![
+        "1 / 0",
+        "1 // 0",
+        "1.5m % 0",
+        "79228162514264337593543950335m + 1",
+        "100000000000000000000 % 0",
+        "[x] % 0",
+        "\"a\" * 2",
+        "[x] + 1 / 0",
+        "if(1, 2, 3)",
+        "!5",
+        "-foo(1)",
+        // Whether these fail depends on the options they are evaluated with.
+        "1 / 0 > 2",
+        "0 * (1 / 0.0) ?? 1",
+    ]
+    .into_iter();
+
+    for input in tests {
+        let expression = parse_input(input).unwrap();
+        assert_eq!(expression, optimize(&expression), "{input}");
+    }
+}
+
+#[test]
+fn partial_eval_leaves_unknown_parameters() {
+    let context = Context::from_map(HashMap::from_iter([
+        ("rate".to_string(), Object::Double(0.5)),
+        ("enabled".to_string(), Object::Bool(true)),
+        ("limit".to_string(), Object::Int(-3)),
+        ("strict".to_string(), Object::Bool(false)),
+        (
+            "order".to_string(),
+            Object::Map(Rc::new(HashMap::from_iter([
+                ("total".to_string(), Object::Int(10)),
+                ("customer".to_string(), Object::Map(Rc::default())),
+            ]))),
+        ),
+    ]));
+    let tests = vec![
+        ("[amount] * [rate] + 2 * 3", "[amount] * 0.5 + 6.0"),
+        (
+            "[order].total * 2 + [order].customer.id",
+            "20.0 + [order].customer.id",
+        ),
+        ("[other].total", "[other].total"),
+        ("let r = [rate] * 2 in r + [amount]", "1.0 + [amount]"),
+        (
+            "let a = [amount] * [rate] in a + a",
+            "let a = [amount] * 0.5 in a + a",
+        ),
+        // The let hides [rate] in its body.
+        (
+            "let rate = [amount] in rate + [rate]",
+            "let rate = [amount] in rate + [rate]",
+        ),
+        ("if([enabled], [amount], 0)", "[amount]"),
+        ("if([other], [rate], [limit])", "if([other], 0.5, -3)"),
+        ("[strict] and [amount] > 2", "false"),
+        // Unlike optimize, the bool check on the right operand is kept.
+        ("[enabled] and [flag]", "true and [flag]"),
+        ("[enabled] and [limit] < 0", "true"),
+        ("[amount] * 1", "[amount] * 1"),
+        // Inside the lambda, [rate] is the lambda parameter.
+        (
+            "map([items], rate => rate * [rate] * (1 + 1))",
+            "map([items], rate => rate * [rate] * 2.0)",
+        ),
+    ]
+    .into_iter();
+
+    for (input, expected) in tests {
+        let expression = parse_input(input).unwrap();
+        assert_eq!(
+            parse_input(expected).unwrap(),
+            partial_eval(&expression, context.clone()),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn partial_eval_agrees_with_eval() {
+    let known = HashMap::from_iter([
+        ("a".to_string(), Object::Int(4)),
+        ("b".to_string(), Object::Bool(false)),
+    ]);
+    let mut all = known.clone();
+    all.insert("c".to_string(), Object::Double(1.5));
+    let input = "if([b] or [c] > 1, [a] * [c] - 2, -[a])";
+
+    let residual = partial_eval(&parse_input(input).unwrap(), Context::from_map(known));
+    assert_eq!(
+        evaluator::eval_input(input, Context::from_map(all.clone())).unwrap(),
+        evaluator::eval(&residual, Context::from_map(all)).unwrap()
+    );
+}
+
+#[test]
+fn partial_eval_folds_with_the_context_options() {
+    let options = EvalOptions {
+        promote_to_big_int: true,
+        ..EvalOptions::default()
+    };
+    let context = Context::from_map(HashMap::from_iter([("a".to_string(), Object::Int(5))]))
+        .with_options(options);
+    let input = "2 * 3 + [a]";
+
+    let residual = partial_eval(&parse_input(input).unwrap(), context.clone());
+    assert_eq!(parse_input("11").unwrap(), residual);
+    assert_eq!(
+        evaluator::eval_input(input, context.clone()).unwrap(),
+        evaluator::eval(&residual, context).unwrap()
+    );
+}
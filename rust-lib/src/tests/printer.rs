@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::{
+    object::{Context, Object},
+    optimizer::partial_eval,
+    parser::{parse_input, parse_script},
+    rewrite::{substitute, Substitution},
+};
+
+#[test]
+fn prints_canonical_source() {
+    let tests = vec![
+        ("1+2*3", "1 + 2 * 3"),
+        ("(1+2)*3", "(1 + 2) * 3"),
+        ("1 - (2 - 3)", "1 - (2 - 3)"),
+        ("(1 - 2) - 3", "1 - 2 - 3"),
+        ("((([x])))", "[x]"),
+        ("-(1 + y)", "-(1 + y)"),
+        ("- 4 * 2", "-4 * 2"),
+        ("!(true)", "!true"),
+        ("not(1 < 2)", "not (1 < 2)"),
+        ("[a] or [b]  and [c]", "[a] or [b] and [c]"),
+        ("([a] or [b]) && [c]", "([a] or [b]) && [c]"),
+        ("1 <> 2 == false", "1 <> 2 == false"),
+        ("5 % 2", "5 % 2"),
+        ("7//2*(3//2)", "7 // 2 * (3 // 2)"),
+        ("2**3**2", "2 ** 3 ** 2"),
+        ("(2 ** 3) ** 2", "(2 ** 3) ** 2"),
+        ("(-2) ** -(1 ** 2)", "-2 ** -(1 ** 2)"),
+        ("-(2 ** 2)", "-(2 ** 2)"),
+        (
+            "if( [x] >= 3,max(1,2),  \"a\\\"b\\n\")",
+            "if([x] >= 3, max(1, 2), \"a\\\"b\\n\")",
+        ),
+        ("f()", "f()"),
+        ("2.50", "2.5"),
+        ("3.0", "3.0"),
+        ("2.50M", "2.50m"),
+        ("00100000000000000000000", "100000000000000000000"),
+        ("-(1.5m) + 0m", "-1.5m + 0m"),
+        ("([a] ?? [b] or [c]) ?? 1+2", "[a] ?? [b] or [c] ?? 1 + 2"),
+        ("[a] ?? ([b] ?? [c])", "[a] ?? ([b] ?? [c])"),
+        ("[c]in(\"US\",\"CA\")", "[c] in (\"US\", \"CA\")"),
+        ("in([c], 1 + 1, 3)", "[c] in (1 + 1, 3)"),
+        ("not in([c])", "[c] not in ()"),
+        ("{ 1,2 }[0]", "{1, 2}[0]"),
+        ("[x][ 1+1 ][0]", "[x][1 + 1][0]"),
+        ("(-[x])[0] in {1}", "(-[x])[0] in (1)"),
+        ("[x] in (([a] ?? [b]))", "[x] in (([a] ?? [b]))"),
+        ("[x] not in ( ( -[l] )[0] )", "[x] not in ((-[l])[0])"),
+        ("[x] in ((1), 2)", "[x] in (1, 2)"),
+        ("map([a], (x) => x*2)", "map([a], x => x * 2)"),
+        (
+            "reduce([a],0,(acc,x)=>acc+x)",
+            "reduce([a], 0, (acc, x) => acc + x)",
+        ),
+        ("f(() => 1)", "f(() => 1)"),
+        ("[customer] . address.city", "[customer].address.city"),
+        ("[customer] ?. address?.city", "[customer]?.address?.city"),
+        ("(1)?.x + (-[x])?.y", "1?.x + (-[x])?.y"),
+        ("(-[x]).y + (1).z", "(-[x]).y + (1).z"),
+        (
+            "(99999999999999999999).Length + (1.5m).Length",
+            "(99999999999999999999).Length + (1.5m).Length",
+        ),
+        ("f(x).y[0].z", "f(x).y[0].z"),
+        ("\"abc\" . ToUpper( )", "\"abc\".ToUpper()"),
+        ("([a] + b).Trim().Length", "([a] + b).Trim().Length"),
+        (
+            "let net=[price]*2 in net+net*[tax]",
+            "let net = [price] * 2 in net + net * [tax]",
+        ),
+        ("let a = 1, b = a in b", "let a = 1 in let b = a in b"),
+        (
+            "let ok = ([a] in (1, 2)) in ok",
+            "let ok = ([a] in (1, 2)) in ok",
+        ),
+        ("(let a = 1 in a) + 1", "(let a = 1 in a) + 1"),
+        (
+            "$\"{{x}}\\t{ [a]+1 :0.00} {f( \"}\" )}\"",
+            "$\"{{x}}\\t{[a] + 1:0.00} {f(\"}\")}\"",
+        ),
+    ]
+    .into_iter();
+
+    for (input, expected) in tests {
+        assert_eq!(expected, parse_input(input).unwrap().to_string(), "{input}");
+    }
+}
+
+#[test]
+fn printed_source_parses_to_the_same_expression() {
+    let tests = vec![
+        "1 + 2 * 3 - 4 / 5 % 6",
+        "(1 + 2) * (3 - 4) / (5 % 6)",
+        "1 - (2 + 3) - (4 - 5)",
+        "--1",
+        "(1 ** 2) ** (3 ** 4 ** 5) * 6 ** 7",
+        "-(-[x])",
+        "!![a] == (not [b])",
+        "[a] < 1 == [b] >= 2 and ([c] or [d]) || e",
+        "if([a] and ([b] or [c]), f(g(1), -2.5), \"\\\\ \\t \\r\")",
+        "12345678901.0 * 0.000001",
+        "-0.10m ** 2m - 79228162514264337593543950335m",
+        "-9223372036854775808 * 100000000000000000000 ** 2",
+        "let a = (let b = [x] in b in (1)) in a in {a}",
+        "let f = (x => x in [l]) in map([l], f)",
+        "let a = -(1 in {2}) in f(a in {1}, {a in (1)}, [l][a in (1)])",
+        "[x] in ([a] ?? [b]) or [x] in (([a] ?? [b])) or [x] in (([a] in [b]))",
+        "(99999999999999999999).Length * (2m).Length",
+        "[x] in (((-[l]).Items)) and [x] in ((-[l]).Items) and [x] in ((([l])))",
+    ]
+    .into_iter();
+
+    for input in tests {
+        let expression = parse_input(input).unwrap();
+        let printed = expression.to_string();
+        assert_eq!(
+            expression,
+            parse_input(&printed).unwrap(),
+            "{input} => {printed}"
+        );
+    }
+}
+
+#[test]
+fn prints_loose_right_operands_of_in() {
+    let tests = vec![
+        ("[x] in [l]", "[l]", "[a] ?? [b]", "[x] in (([a] ?? [b]))"),
+        ("[x] in ([l])", "[l]", "[a] ?? [b]", "[x] in ([a] ?? [b])"),
+        (
+            "[x] not in [l]",
+            "[l]",
+            "(-[y])[0]",
+            "[x] not in (((-[y])[0]))",
+        ),
+        (
+            "[x] in [l]",
+            "[l]",
+            "let y = [a] in {y}",
+            "[x] in ((let y = [a] in {y}))",
+        ),
+    ]
+    .into_iter();
+
+    for (input, parameter, replacement, expected) in tests {
+        let substitutions = HashMap::from_iter([(
+            parameter.trim_matches(['[', ']']).to_string(),
+            Substitution::Replace(parse_input(replacement).unwrap()),
+        )]);
+        let expression = substitute(parse_input(input).unwrap(), &substitutions).unwrap();
+        let printed = expression.to_string();
+        assert_eq!(expected, printed, "{input}");
+        assert_eq!(expression, parse_input(&printed).unwrap(), "{input}");
+    }
+}
+
+#[test]
+fn prints_partially_evaluated_expressions() {
+    let context = Context::from_map(HashMap::from_iter([
+        ("rate".to_string(), Object::Double(-0.5)),
+        ("fee".to_string(), Object::Int(3)),
+    ]));
+    let residual = partial_eval(
+        &parse_input("([amount] + [fee]) * [rate] - [fee] * 2").unwrap(),
+        context,
+    );
+    assert_eq!("([amount] + 3) * -0.5 - 6.0", residual.to_string());
+}
+
+#[test]
+fn prints_scripts() {
+    let script = parse_script("total=[a]+[b] ; [flag] = total>100;").unwrap();
+    let printed = script.to_string();
+    assert_eq!("[total] = [a] + [b];\n[flag] = total > 100;", printed);
+    assert_eq!(script, parse_script(&printed).unwrap());
+}
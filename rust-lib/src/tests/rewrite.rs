@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use crate::{
+    object::EvalOptions,
+    parser::parse_input,
+    rewrite::{substitute_input, substitute_input_with, Substitution, SubstitutionError},
+};
+
+fn substitutions(entries: &[(&str, Substitution)]) -> HashMap<String, Substitution> {
+    entries
+        .iter()
+        .map(|(name, substitution)| (name.to_string(), substitution.clone()))
+        .collect()
+}
+
+#[test]
+fn renames_parameters() {
+    let substitutions = substitutions(&[
+        ("old_name", Substitution::Rename("new_name".to_string())),
+        ("total", Substitution::Rename("grand_total".to_string())),
+    ]);
+    assert_eq!(
+        "if([new_name] > 1, grand_total, old_name([other]))",
+        substitute_input("if([old_name]>1, total, old_name([other]))", &substitutions).unwrap()
+    );
+}
+
+#[test]
+fn replaces_parameters_with_expressions() {
+    let substitutions = substitutions(&[
+        (
+            "net",
+            Substitution::Replace(parse_input("[price] - [discount]").unwrap()),
+        ),
+        (
+            "price",
+            Substitution::Replace(parse_input("[unit] * [qty]").unwrap()),
+        ),
+    ]);
+    // The replacement for [net] is not substituted again.
+    assert_eq!(
+        "([price] - [discount]) * [tax] + [unit] * [qty]",
+        substitute_input("[net] * [tax] + [price]", &substitutions).unwrap()
+    );
+    assert_eq!(
+        "-([price] - [discount])",
+        substitute_input("-[net]", &substitutions).unwrap()
+    );
+}
+
+#[test]
+fn reads_the_input_the_way_the_options_ask() {
+    let substitutions = substitutions(&[("x", Substitution::Rename("y".to_string()))]);
+    let options = EvalOptions {
+        decimal_as_default: true,
+        caret_power: true,
+        ..EvalOptions::default()
+    };
+    assert_eq!(
+        "[y] ** 2 * 0.5m",
+        substitute_input_with("[x] ^ 2 * 0.5", &substitutions, &options).unwrap()
+    );
+}
+
+#[test]
+fn leaves_lambda_parameters_alone() {
+    let substitutions = substitutions(&[("x", Substitution::Rename("y".to_string()))]);
+    assert_eq!(
+        "map([y], x => x * 2) == y",
+        substitute_input("map([x], x => x * 2) == x", &substitutions).unwrap()
+    );
+}
+
+#[test]
+fn leaves_let_names_alone() {
+    let substitutions = substitutions(&[("x", Substitution::Rename("y".to_string()))]);
+    assert_eq!(
+        "let x = [y] + 1 in x * [x] + (let z = x in x)",
+        substitute_input(
+            "let x = [x] + 1 in x * [x] + (let z = x in x)",
+            &substitutions
+        )
+        .unwrap()
+    );
+    assert_eq!(
+        "(let x = 1 in x) + y",
+        substitute_input("(let x = 1 in x) + x", &substitutions).unwrap()
+    );
+}
+
+#[test]
+fn renames_bindings_that_would_capture_substituted_names() {
+    let rename = substitutions(&[("rate", Substitution::Rename("x".to_string()))]);
+    assert_eq!(
+        "map([items], x_1 => x_1 * [x])",
+        substitute_input("map([items], x => x * [rate])", &rename).unwrap()
+    );
+    assert_eq!(
+        "map([items], x => x * 2) + [x]",
+        substitute_input("map([items], x => x * 2) + [rate]", &rename).unwrap()
+    );
+    assert_eq!(
+        "reduce([items], 0, (x_2, y) => x_2 + y * [x] + [x_1])",
+        substitute_input(
+            "reduce([items], 0, (x, y) => x + y * [rate] + [x_1])",
+            &rename
+        )
+        .unwrap()
+    );
+
+    let replace = substitutions(&[(
+        "rate",
+        Substitution::Replace(parse_input("[x] + 1").unwrap()),
+    )]);
+    assert_eq!(
+        "let x_1 = 2 in x_1 * ([x] + 1)",
+        substitute_input("let x = 2 in x * [rate]", &replace).unwrap()
+    );
+    assert_eq!(
+        "let x = [x] + 1 in x * 2",
+        substitute_input("let x = [rate] in x * 2", &replace).unwrap()
+    );
+}
+
+#[test]
+fn rejects_invalid_names() {
+    let substitutions = substitutions(&[("a", Substitution::Rename("not valid".to_string()))]);
+    assert!(matches!(
+        substitute_input("[a]", &substitutions),
+        Err(SubstitutionError::InvalidIdentifier(_))
+    ));
+}
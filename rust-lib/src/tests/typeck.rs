@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    evaluator::eval_input,
+    expression::Span,
+    object::{Context, EvalOptions, Object},
+    token::Token,
+    typeck::{check_input, FunctionSignature, Type, TypeErrorKind, TypeSchema},
+};
+
+fn schema() -> TypeSchema {
+    TypeSchema::new()
+        .with_parameter("name", Type::Str)
+        .with_parameter("price", Type::Double)
+        .with_parameter("qty", Type::Int)
+        .with_parameter("vip", Type::Bool)
+        .with_parameter("tags", Type::List(Box::new(Type::Str)))
+        .with_parameter(
+            "customer",
+            Type::Map(BTreeMap::from_iter([
+                ("name".to_string(), Type::Str),
+                (
+                    "address".to_string(),
+                    Type::Map(BTreeMap::from_iter([("city".to_string(), Type::Str)])),
+                ),
+            ])),
+        )
+        .with_function(
+            "round",
+            FunctionSignature {
+                parameters: vec![Type::Double, Type::Int],
+                returns: Type::Double,
+            },
+        )
+}
+
+#[test]
+fn computes_result_type() {
+    let tests = vec![
+        ("[qty]", Type::Int),
+        ("-[qty]", Type::Int),
+        ("[price] * [qty]", Type::Double),
+        ("[qty] ** 2", Type::Double),
+        ("2 ** 3", Type::Int),
+        ("2 ** 64", Type::Double),
+        ("2 ** -1", Type::Double),
+        ("[qty] ** [qty]", Type::Double),
+        ("[qty] // 2 % [qty]", Type::Int),
+        ("[price] // 2", Type::Double),
+        ("[qty] % 2.5", Type::Double),
+        ("[price] * 2m + [qty]", Type::Decimal),
+        ("-1.5m // [qty]", Type::Decimal),
+        ("2m ** [qty]", Type::Decimal),
+        ("2m ** 0.5", Type::Double),
+        ("if([vip], [qty], 0.5m)", Type::Decimal),
+        ("Sum({1.5m, [qty]})", Type::Decimal),
+        ("100000000000000000000 * [qty] // 2", Type::BigInt),
+        ("100000000000000000000 / [qty]", Type::Double),
+        ("[qty] ** 100000000000000000000", Type::BigInt),
+        ("100000000000000000000 ** 2", Type::BigInt),
+        ("100000000000000000000 ** -1", Type::Double),
+        ("100000000000000000000 ** [qty]", Type::Double),
+        ("100000000000000000000 + [price]", Type::Double),
+        ("if([vip], [qty], 100000000000000000000)", Type::BigInt),
+        ("[qty] ** null", Type::Null),
+        ("[qty] > 2 and ![vip]", Type::Bool),
+        ("[qty] <> 2", Type::Bool),
+        ("round([qty], 2)", Type::Double),
+        ("if([vip], [name], \"none\")", Type::Str),
+        ("if([vip], [qty], 0.5)", Type::Double),
+        ("[qty] + null", Type::Null),
+        ("null and [vip]", Type::Bool),
+        ("isnull([name])", Type::Bool),
+        ("ifnull([price], 0)", Type::Double),
+        ("coalesce(null, [name], \"none\")", Type::Str),
+        ("if([vip], null, [qty])", Type::Int),
+        ("[price] ?? [qty]", Type::Double),
+        ("[name] in (\"a\", null)", Type::Bool),
+        ("[qty] not in (1, 2.5)", Type::Bool),
+        ("{[qty], null}", Type::List(Box::new(Type::Int))),
+        ("[tags][0]", Type::Str),
+        ("[name] in [tags]", Type::Bool),
+        ("Sum({[qty], [price]})", Type::Double),
+        ("Max([qty], 2)", Type::Int),
+        ("Sort([tags])", Type::List(Box::new(Type::Str))),
+        ("Count([tags]) > 1 and Any([vip], null)", Type::Bool),
+        (
+            "map([tags], name => name == [name])",
+            Type::List(Box::new(Type::Bool)),
+        ),
+        (
+            "filter({[qty]}, qty => qty > 1)",
+            Type::List(Box::new(Type::Int)),
+        ),
+        ("reduce({1, 2}, 0, (sum, x) => sum + x)", Type::Double),
+        ("[customer].address.city in [tags]", Type::Bool),
+        ("[customer]?.address?.zip", Type::Null),
+        ("[customer]?.name ?? \"none\"", Type::Str),
+        ("null.name", Type::Null),
+        ("Round([price], 2)", Type::Double),
+        ("Floor(2.5m)", Type::Decimal),
+        ("[qty].Truncate()", Type::Int),
+        ("Ceiling(null)", Type::Null),
+        ("Round([price], null)", Type::Null),
+        ("[name].Trim().ToUpper()", Type::Str),
+        ("[customer].name.Length > [tags].Length", Type::Bool),
+        ("[tags].map(t => t.Length).Sum()", Type::Double),
+        ("let total = [qty] * [price] in total > 10", Type::Bool),
+        ("let qty = [name] in qty.Length", Type::Int),
+        ("$\"{[name]} x{[qty]:D3} {[vip]} {null:F2}\"", Type::Str),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert_eq!(expected, check_input(input, &schema()).unwrap(), "{input}");
+    }
+}
+
+#[test]
+fn reports_operator_errors_with_spans() {
+    let errors = check_input("[name] * 2", &schema()).unwrap_err();
+    assert_eq!(1, errors.len());
+    assert_eq!(Span { start: 0, end: 10 }, errors[0].span);
+    assert!(matches!(
+        errors[0].kind,
+        TypeErrorKind::UnExpectedOperatorOperandFound {
+            operator: Token::Asterisk,
+            left: Type::Str,
+            right: Type::Int,
+        }
+    ));
+}
+
+#[test]
+fn reports_missing_members() {
+    let errors = check_input("[customer].address.zip + [qty].name", &schema()).unwrap_err();
+    let found: Vec<(Span, String)> = errors
+        .iter()
+        .map(|error| (error.span, format!("{:?}", error.kind)))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                Span { start: 0, end: 22 },
+                "UnknownMember(\"[customer].address.zip\")".to_string()
+            ),
+            (
+                Span { start: 25, end: 30 },
+                "ExpectedTypeXFoundY { expected: \"map\", found: Int }".to_string()
+            ),
+        ],
+        found
+    );
+}
+
+#[test]
+fn checks_method_calls_as_function_calls() {
+    let errors = check_input("[qty].ToUpper() + [name].round(1)", &schema()).unwrap_err();
+    let found: Vec<(Span, String)> = errors
+        .iter()
+        .map(|error| (error.span, format!("{:?}", error.kind)))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                Span { start: 0, end: 5 },
+                "ExpectedTypeXFoundY { expected: \"string\", found: Int }".to_string()
+            ),
+            (
+                Span { start: 18, end: 24 },
+                "ExpectedTypeXFoundY { expected: \"double\", found: Str }".to_string()
+            ),
+        ],
+        found
+    );
+}
+
+#[test]
+fn checks_rounding_function_arguments() {
+    let errors = check_input(
+        "Round([name]) + Round([price], 1.5) + Floor([price], 1)",
+        &schema(),
+    )
+    .unwrap_err();
+    let found: Vec<(Span, String)> = errors
+        .iter()
+        .map(|error| (error.span, format!("{:?}", error.kind)))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                Span { start: 6, end: 12 },
+                "ExpectedTypeXFoundY { expected: \"number\", found: Str }".to_string()
+            ),
+            (
+                Span { start: 31, end: 34 },
+                "ExpectedTypeXFoundY { expected: \"int\", found: Double }".to_string()
+            ),
+            (
+                Span { start: 38, end: 55 },
+                "MissMatchFunctionArguements { expected: 1, found: 2 }".to_string()
+            ),
+        ],
+        found
+    );
+}
+
+#[test]
+fn reports_all_errors() {
+    let errors =
+        check_input("if([qty], [missing], round([name], 1)) + foo(1)", &schema()).unwrap_err();
+    let found: Vec<(Span, String)> = errors
+        .iter()
+        .map(|error| (error.span, format!("{:?}", error.kind)))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                Span { start: 3, end: 8 },
+                "ExpectedTypeXFoundY { expected: \"bool\", found: Int }".to_string()
+            ),
+            (
+                Span { start: 10, end: 19 },
+                "UnknownParameter(\"missing\")".to_string()
+            ),
+            (
+                Span { start: 27, end: 33 },
+                "ExpectedTypeXFoundY { expected: \"double\", found: Str }".to_string()
+            ),
+            (
+                Span { start: 41, end: 44 },
+                "UnknownFunction(\"foo\")".to_string()
+            ),
+        ],
+        found
+    );
+}
+
+#[test]
+fn checks_template_values() {
+    let errors = check_input("$\"{[tags]} {[name]:0.00} {[qty]:N0}\"", &schema()).unwrap_err();
+    let found: Vec<(Span, String)> = errors
+        .iter()
+        .map(|error| (error.span, format!("{:?}", error.kind)))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                Span { start: 3, end: 9 },
+                "ExpectedTypeXFoundY { expected: \"number, string or bool\", found: List(Str) }"
+                    .to_string()
+            ),
+            (
+                Span { start: 12, end: 18 },
+                "ExpectedTypeXFoundY { expected: \"number\", found: Str }".to_string()
+            ),
+        ],
+        found
+    );
+}
+
+#[test]
+fn reads_the_input_the_way_the_options_ask() {
+    let schema = schema().with_options(EvalOptions {
+        decimal_as_default: true,
+        caret_power: true,
+        ..EvalOptions::default()
+    });
+    let tests = vec![
+        ("[price] + 0.1", Type::Decimal),
+        ("0.1 + 0.2 == 0.3", Type::Bool),
+        ("2 ^ 3", Type::Int),
+        ("2 ^ 3 * 0.5", Type::Decimal),
+    ]
+    .into_iter();
+    for (input, expected) in tests {
+        assert_eq!(expected, check_input(input, &schema).unwrap(), "{input}");
+    }
+}
+
+#[test]
+fn types_what_the_evaluator_gives_under_the_options() {
+    let context = Context::from_map(HashMap::from_iter([
+        ("price".to_string(), Object::Double(2.5)),
+        ("qty".to_string(), Object::Int(3)),
+        ("vip".to_string(), Object::Bool(true)),
+    ]));
+    let tests = vec![
+        "[qty] + 1",
+        "[qty] * [qty] - 2",
+        "[qty] / 2",
+        "[qty] * [price]",
+        "2 * 3",
+        "9223372036854775807 + 1",
+        "99999999999999999999 - 99999999999999999998",
+        "99999999999999999999 - 99999999999999999998 + [qty]",
+        "-9223372036854775808",
+        "-99999999999999999999",
+        "2 ** 0.5m",
+        "2 ** 2m",
+        "[qty] ** 0.5m",
+        "Sum({1, 2})",
+        "Sum({[qty], null})",
+        "Sum({[qty], [price]})",
+        "Avg({1, [qty]})",
+        "reduce({1, 2}, 0, (sum, x) => sum + x)",
+        "if([vip], [qty] - 1, 0)",
+    ]
+    .into_iter();
+    for promote_to_big_int in [false, true] {
+        let options = EvalOptions {
+            promote_to_big_int,
+            ..EvalOptions::default()
+        };
+        let schema = schema().with_options(options);
+        for input in tests.clone() {
+            let evaluated = match eval_input(input, context.clone().with_options(options)).unwrap()
+            {
+                Object::Int(_) => Type::Int,
+                Object::Double(_) => Type::Double,
+                Object::Decimal(_) => Type::Decimal,
+                Object::BigInt(_) => Type::BigInt,
+                value => panic!("{input} evaluated to {value:?}"),
+            };
+            assert_eq!(
+                evaluated,
+                check_input(input, &schema).unwrap(),
+                "{input} with {options:?}"
+            );
+        }
+    }
+}
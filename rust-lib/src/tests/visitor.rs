@@ -0,0 +1,73 @@
+use std::rc::Rc;
+
+use crate::{
+    expression::Expression,
+    parser::parse_input,
+    token::Token,
+    visitor::{fold_infix_expression, walk_infix_expression, Fold, Visitor},
+};
+
+#[derive(Default)]
+struct OperatorCounter {
+    operators: Vec<Token>,
+    literals: usize,
+}
+
+impl Visitor for OperatorCounter {
+    fn visit_infix_expression(&mut self, operator: &Token, left: &Expression, right: &Expression) {
+        self.operators.push(operator.clone());
+        walk_infix_expression(self, operator, left, right);
+    }
+
+    fn visit_literal(&mut self, _literal: &Expression) {
+        self.literals += 1;
+    }
+}
+
+#[test]
+fn visitor_walks_every_node() {
+    let mut counter = OperatorCounter::default();
+    counter.visit_expression(&parse_input("if(-[a] > 1, f(2 * 3), \"x\") + 4").unwrap());
+    assert_eq!(
+        vec![Token::Plus, Token::GreaterThan, Token::Asterisk],
+        counter.operators
+    );
+    assert_eq!(5, counter.literals);
+}
+
+struct Rewriter;
+
+impl Fold for Rewriter {
+    fn fold_ident(&mut self, ident: Rc<String>) -> Expression {
+        Expression::Ident(Rc::new(format!("new_{}", ident)))
+    }
+
+    fn fold_function_name(&mut self, name: Rc<String>, arity: usize) -> Rc<String> {
+        match (name.as_str(), arity) {
+            ("max", 2) => Rc::new("Max".to_string()),
+            _ => name,
+        }
+    }
+
+    fn fold_infix_expression(
+        &mut self,
+        operator: Token,
+        left: Expression,
+        right: Expression,
+    ) -> Expression {
+        let operator = match operator {
+            Token::DoubleAmpersand => Token::And,
+            operator => operator,
+        };
+        fold_infix_expression(self, operator, left, right)
+    }
+}
+
+#[test]
+fn fold_rebuilds_the_tree() {
+    let expression = parse_input("max([a], [max]) > 1 && !b && max(1)").unwrap();
+    assert_eq!(
+        parse_input("Max([new_a], [new_max]) > 1 and !b and max(1)").unwrap(),
+        Rewriter.fold_expression(expression)
+    );
+}
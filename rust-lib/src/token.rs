@@ -1,17 +1,26 @@
 #![allow(dead_code)]
 
-use std::rc::Rc;
+use std::{fmt, rc::Rc};
+
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
 
 use crate::expression::Precedence;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token {
     // Data Types
     IntLiteral(i64),
     DoubleLiteral(f64),
+    DecimalLiteral(Decimal),   // 1.5m
+    BigIntLiteral(Rc<BigInt>), // an integer beyond i64
     True,
     False,
+    Null,
     StringLiteral(Rc<String>),
+    Template(Rc<Vec<TemplateToken>>), // $"text {expression:format}"
     // TODO: this also should be wrapped in Rc.
     DateTime(String),
 
@@ -24,16 +33,24 @@ pub enum Token {
 
     LBracket, // '['
     RBracket, // ']'
+    LBrace,   // '{'
+    RBrace,   // '}'
     Comma,
+    Dot,       // '.'
+    Semicolon, // ';'
 
     // Logical Negation
     Bang, // !
     Not,  // not
 
     // Multiplicative Operators
-    Asterisk, // *
-    Slash,    // '/'
-    Percent,  // '%'
+    Asterisk,    // *
+    Slash,       // '/'
+    DoubleSlash, // '//', integer division
+    Percent,     // '%'
+
+    // Exponent operator
+    DoubleAsterisk, // '**'
 
     // Additive operators
     Plus,
@@ -54,22 +71,159 @@ pub enum Token {
 
     Or,         // 'or'
     DoublePipe, // '||'
+
+    DoubleQuestionMark, // '??'
+    QuestionDot,        // '?.'
+
+    In,    // 'in'
+    NotIn, // 'not in'
+
+    Arrow, // '=>'
+
+    Let,    // 'let'
+    Assign, // '='
     EOF,
     Illegal,
 }
 
+/// A piece of a `$"..."` template literal.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub enum TemplateToken {
+    Text(Rc<String>),
+    /// The source of a `{expression:format}` hole, `offset` being where the
+    /// expression starts in the input.
+    Hole {
+        source: Rc<String>,
+        offset: usize,
+        format: Option<Rc<String>>,
+    },
+}
+
 impl Token {
+    pub(crate) fn is_prefix_operator(&self) -> bool {
+        matches!(self, Token::Minus | Token::Not | Token::Bang)
+    }
+
+    pub(crate) fn is_infix_operator(&self) -> bool {
+        matches!(
+            self,
+            Token::Plus
+                | Token::Minus
+                | Token::Asterisk
+                | Token::Slash
+                | Token::DoubleSlash
+                | Token::Percent
+                | Token::DoubleAsterisk
+                | Token::Equals
+                | Token::NotEquals
+                | Token::NotEqualsAngleBrackets
+                | Token::LessThan
+                | Token::LessThanEqualTo
+                | Token::GreaterThan
+                | Token::GreaterThanEqualTo
+                | Token::Or
+                | Token::DoublePipe
+                | Token::And
+                | Token::DoubleAmpersand
+                | Token::DoubleQuestionMark
+                | Token::In
+                | Token::NotIn
+        )
+    }
+
     pub(crate) fn get_precedence(&self) -> Precedence {
         match self {
-            Token::Equals | Token::NotEquals | Token::DoublePipe | Token::Or => Precedence::Equals,
+            Token::DoubleQuestionMark => Precedence::NullCoalescing,
+            Token::DoublePipe | Token::Or => Precedence::LogicalOr,
+            Token::DoubleAmpersand | Token::And => Precedence::LogicalAnd,
+            Token::Equals | Token::NotEquals | Token::NotEqualsAngleBrackets => Precedence::Equals,
             Token::LessThan
             | Self::LessThanEqualTo
             | Token::GreaterThanEqualTo
-            | Token::GreaterThan => Precedence::LessGreater,
+            | Token::GreaterThan
+            | Token::In
+            | Token::NotIn => Precedence::LessGreater,
             Token::Plus | Token::Minus => Precedence::Sum,
-            Token::Slash | Token::Asterisk => Precedence::Product,
-            Token::LParen => Precedence::Call,
+            Token::Slash | Token::DoubleSlash | Token::Asterisk | Token::Percent => {
+                Precedence::Product
+            }
+            Token::DoubleAsterisk => Precedence::Exponent,
+            // A '[' after an expression indexes it, a '.' or '?.' accesses a member.
+            Token::LParen | Token::LBracket | Token::Dot | Token::QuestionDot => Precedence::Call,
             _ => Precedence::Lowest,
         }
     }
 }
+
+impl fmt::Display for Token {
+    /// Writes the token the way it is spelled in the source.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Token::IntLiteral(int) => return write!(f, "{}", int),
+            Token::DoubleLiteral(double) => return write!(f, "{}", double),
+            Token::DecimalLiteral(decimal) => return write!(f, "{}m", decimal),
+            Token::BigIntLiteral(int) => return write!(f, "{}", int),
+            Token::StringLiteral(string) => return write!(f, "{:?}", string),
+            Token::Template(parts) => {
+                f.write_str("$\"")?;
+                for part in parts.iter() {
+                    match part {
+                        TemplateToken::Text(text) => f.write_str(text)?,
+                        TemplateToken::Hole {
+                            source,
+                            format: Some(format),
+                            ..
+                        } => write!(f, "{{{}:{}}}", source, format)?,
+                        TemplateToken::Hole { source, .. } => write!(f, "{{{}}}", source)?,
+                    }
+                }
+                return f.write_str("\"");
+            }
+            Token::DateTime(date_time) => return write!(f, "#{}#", date_time),
+            Token::Ident(ident) => return write!(f, "{}", ident),
+            Token::True => "true",
+            Token::False => "false",
+            Token::Null => "null",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::LBracket => "[",
+            Token::RBracket => "]",
+            Token::LBrace => "{",
+            Token::RBrace => "}",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::Semicolon => ";",
+            Token::Bang => "!",
+            Token::Not => "not",
+            Token::Asterisk => "*",
+            Token::Slash => "/",
+            Token::DoubleSlash => "//",
+            Token::Percent => "%",
+            Token::DoubleAsterisk => "**",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::LessThan => "<",
+            Token::GreaterThan => ">",
+            Token::LessThanEqualTo => "<=",
+            Token::GreaterThanEqualTo => ">=",
+            Token::Equals => "==",
+            Token::NotEquals => "!=",
+            Token::NotEqualsAngleBrackets => "<>",
+            Token::And => "and",
+            Token::DoubleAmpersand => "&&",
+            Token::Or => "or",
+            Token::DoublePipe => "||",
+            Token::DoubleQuestionMark => "??",
+            Token::QuestionDot => "?.",
+            Token::In => "in",
+            Token::NotIn => "not in",
+            Token::Arrow => "=>",
+            Token::Let => "let",
+            Token::Assign => "=",
+            Token::EOF => "",
+            Token::Illegal => "<illegal>",
+        };
+        f.write_str(text)
+    }
+}
@@ -0,0 +1,1028 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    evaluator,
+    expression::{Expression, ParseExpressionError, Span, SpanTree, TemplatePart},
+    object::{Context, EvalOptions, Object},
+    parser::{self, Parser},
+    token::Token,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Double,
+    Decimal,
+    /// An integer beyond the range of `Int`. Arithmetic on big integers gives
+    /// an `Int` when the result fits, which is only known for literals, so the
+    /// result is otherwise typed as a big integer, to which an `Int` is
+    /// assignable.
+    BigInt,
+    Bool,
+    Str,
+    DateTime,
+    /// The type of `null`, which can stand in for a value of any other type.
+    Null,
+    /// A list whose elements are all of the given type.
+    List(Box<Type>),
+    /// A map with the given members, e.g. a record passed as a parameter.
+    Map(BTreeMap<String, Type>),
+}
+
+impl Type {
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Type::Int | Type::Double | Type::Decimal | Type::BigInt
+        )
+    }
+
+    /// The type of arithmetic on operands of types `self` and `other`: a
+    /// decimal when either one is, a big integer for a big integer and an
+    /// integer, and a double otherwise.
+    fn arithmetic_result(&self, other: &Type) -> Type {
+        match (self, other) {
+            (Type::Decimal, _) | (_, Type::Decimal) => Type::Decimal,
+            (Type::BigInt, Type::Int | Type::BigInt) | (Type::Int, Type::BigInt) => Type::BigInt,
+            _ => Type::Double,
+        }
+    }
+
+    /// Whether a value of type `self` can be passed where `expected` is declared.
+    fn is_assignable_to(&self, expected: &Type) -> bool {
+        match (self, expected) {
+            (Type::List(found), Type::List(expected)) => found.is_assignable_to(expected),
+            _ => {
+                self == expected
+                    || *self == Type::Null
+                    || (*self == Type::Int && expected.is_numeric())
+            }
+        }
+    }
+
+    /// The type of a value that is either of type `self` or of type `other`.
+    fn unify(self, other: Type) -> Option<Type> {
+        match (self, other) {
+            (this, other) if this == other => Some(this),
+            (Type::Null, other) | (other, Type::Null) => Some(other),
+            (this, other) if this.is_numeric() && other.is_numeric() => {
+                Some(this.arithmetic_result(&other))
+            }
+            (Type::List(this), Type::List(other)) => {
+                Some(Type::List(Box::new(this.unify(*other)?)))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionSignature {
+    pub parameters: Vec<Type>,
+    pub returns: Type,
+}
+
+/// The declared types of the parameters and functions an expression may use,
+/// and the options it is evaluated with.
+#[derive(Debug, Clone, Default)]
+pub struct TypeSchema {
+    parameters: HashMap<String, Type>,
+    functions: HashMap<String, FunctionSignature>,
+    options: EvalOptions,
+}
+
+impl TypeSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_parameter(mut self, name: &str, parameter_type: Type) -> Self {
+        self.parameters.insert(name.to_string(), parameter_type);
+        self
+    }
+
+    pub fn with_function(mut self, name: &str, signature: FunctionSignature) -> Self {
+        self.functions.insert(name.to_string(), signature);
+        self
+    }
+
+    /// The options of the evaluation the checked expressions are meant for.
+    /// [`check_input`] reads the input the way they ask, and the types follow
+    /// them, e.g. a sum of ints is an int when they promote to big integers.
+    pub fn with_options(mut self, options: EvalOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct TypeError {
+    pub span: Span,
+    pub kind: TypeErrorKind,
+}
+
+#[derive(Debug)]
+pub enum TypeErrorKind {
+    ParseExpressionError {
+        error: ParseExpressionError,
+    },
+    UnknownParameter(String),
+    // The member expression, e.g. `[customer].address`, naming the missing member.
+    UnknownMember(String),
+    UnknownFunction(String),
+    MissMatchFunctionArguements {
+        expected: usize,
+        found: usize,
+    },
+    ExpectedTypeXFoundY {
+        expected: &'static str,
+        found: Type,
+    },
+    UnExpectedOperatorOperandFound {
+        operator: Token,
+        left: Type,
+        right: Type,
+    },
+    MissMatchBranchTypes {
+        when_true: Type,
+        when_false: Type,
+    },
+    ExpectedLambda {
+        arity: usize,
+    },
+    // Lambdas are only checked as the argument of `map`, `filter` or `reduce`.
+    UnExpectedLambda,
+}
+
+/// Parses `input` and type checks it against `schema`, see [`check`].
+pub fn check_input(input: &str, schema: &TypeSchema) -> Result<Type, Vec<TypeError>> {
+    let lexer = parser::lexer(input, &schema.options);
+    let (expression, spans) = Parser::new(lexer)
+        .and_then(|mut parser| parser.parse_with_spans())
+        .map_err(|error| {
+            vec![TypeError {
+                span: Span {
+                    start: 0,
+                    end: input.len(),
+                },
+                kind: TypeErrorKind::ParseExpressionError { error },
+            }]
+        })?;
+    check(&expression, &spans, schema)
+}
+
+/// Computes the type `expression` evaluates to, following the evaluator's
+/// rules, or every type error found in it. `spans` is the span tree the parser
+/// returned for `expression`.
+///
+/// An expression that fails to type check does not produce further errors in
+/// the expressions containing it.
+pub fn check(
+    expression: &Expression,
+    spans: &SpanTree,
+    schema: &TypeSchema,
+) -> Result<Type, Vec<TypeError>> {
+    let mut checker = TypeChecker {
+        schema,
+        scopes: vec![],
+        errors: vec![],
+    };
+    match checker.check(expression, spans) {
+        Some(expression_type) if checker.errors.is_empty() => Ok(expression_type),
+        _ => {
+            checker.errors.sort_by_key(|error| error.span.start);
+            Err(checker.errors)
+        }
+    }
+}
+
+struct TypeChecker<'a> {
+    schema: &'a TypeSchema,
+    // Types of the parameters of the lambdas and of the names bound by the lets
+    // being checked, innermost last.
+    scopes: Vec<HashMap<String, Type>>,
+    errors: Vec<TypeError>,
+}
+
+impl TypeChecker<'_> {
+    fn error(&mut self, spans: &SpanTree, kind: TypeErrorKind) -> Option<Type> {
+        self.errors.push(TypeError {
+            span: spans.span,
+            kind,
+        });
+        None
+    }
+
+    /// The type of the value `expression`, an operator on number literals,
+    /// evaluates to, when the evaluator's result type depends on the values,
+    /// e.g. an int for a difference of big integers that fits in one. `None`
+    /// when it fails to evaluate.
+    fn literal_result(&self, expression: Expression) -> Option<Type> {
+        let context = Context::default().with_options(self.schema.options);
+        match evaluator::eval(&expression, context).ok()? {
+            Object::Int(_) => Some(Type::Int),
+            Object::Double(_) => Some(Type::Double),
+            Object::Decimal(_) => Some(Type::Decimal),
+            Object::BigInt(_) => Some(Type::BigInt),
+            Object::Bool(_) => Some(Type::Bool),
+            Object::Null => Some(Type::Null),
+            _ => None,
+        }
+    }
+
+    fn check(&mut self, expression: &Expression, spans: &SpanTree) -> Option<Type> {
+        match expression {
+            Expression::Nil => None,
+            Expression::Int(_) => Some(Type::Int),
+            Expression::Double(_) => Some(Type::Double),
+            Expression::Decimal(_) => Some(Type::Decimal),
+            Expression::BigInt(_) => Some(Type::BigInt),
+            Expression::Bool(_) => Some(Type::Bool),
+            Expression::Str(_) => Some(Type::Str),
+            Expression::Null => Some(Type::Null),
+            Expression::Ident(ident) | Expression::BareIdent(ident) => {
+                let bound = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.get(ident.as_str()));
+                match bound.or_else(|| self.schema.parameters.get(ident.as_str())) {
+                    Some(parameter_type) => Some(parameter_type.clone()),
+                    None => self.error(spans, TypeErrorKind::UnknownParameter(ident.to_string())),
+                }
+            }
+            Expression::PrefixExpression {
+                operator,
+                expression,
+            } => self.check_prefix_expression(operator, expression, spans),
+            Expression::InfixExpression {
+                operator,
+                left,
+                right,
+            } => self.check_infix_expression(operator, left, right, spans),
+            Expression::CallExpression {
+                function,
+                arguments,
+            } => self.check_fn_call(function, arguments, spans),
+            Expression::List(elements) => self.check_list(elements, spans),
+            Expression::IndexExpression { expression, index } => {
+                self.check_index_expression(expression, index, spans)
+            }
+            Expression::MemberExpression {
+                expression: map,
+                member,
+                null_safe,
+            } => match self.check(map, child(spans, 0))? {
+                Type::Map(members) => match members.get(member.as_str()) {
+                    Some(member_type) => Some(member_type.clone()),
+                    None if *null_safe => Some(Type::Null),
+                    None => self.error(spans, TypeErrorKind::UnknownMember(expression.to_string())),
+                },
+                Type::Null => Some(Type::Null),
+                found => self.check_property(member, found, spans),
+            },
+            Expression::Let { name, value, body } => {
+                let value_type = self.check(value, child(spans, 0))?;
+                self.scopes
+                    .push(HashMap::from_iter([(name.to_string(), value_type)]));
+                let body_type = self.check(body, child(spans, 1));
+                self.scopes.pop();
+                body_type
+            }
+            Expression::Template(parts) => self.check_template(parts, spans),
+            Expression::Lambda { .. } => self.error(spans, TypeErrorKind::UnExpectedLambda),
+        }
+    }
+
+    /// The elements of a list must all unify to the same type.
+    fn check_list(&mut self, elements: &[Box<Expression>], spans: &SpanTree) -> Option<Type> {
+        let mut element_type = Some(Type::Null);
+        for (i, element) in elements.iter().enumerate() {
+            let element_spans = child(spans, i);
+            let (Some(found), Some(so_far)) = (self.check(element, element_spans), element_type)
+            else {
+                element_type = None;
+                continue;
+            };
+            element_type = match so_far.clone().unify(found.clone()) {
+                Some(unified) => Some(unified),
+                None => self.error(
+                    element_spans,
+                    TypeErrorKind::ExpectedTypeXFoundY {
+                        expected: type_name(&so_far),
+                        found,
+                    },
+                ),
+            };
+        }
+        Some(Type::List(Box::new(element_type?)))
+    }
+
+    /// The values of a template must have a text form, and be numbers when
+    /// they come with a format.
+    fn check_template(&mut self, parts: &[TemplatePart], spans: &SpanTree) -> Option<Type> {
+        let mut is_valid = true;
+        let values = parts.iter().filter_map(|part| match part {
+            TemplatePart::Value { expression, format } => Some((expression, format)),
+            TemplatePart::Text(_) => None,
+        });
+        for (i, (expression, format)) in values.enumerate() {
+            let value_spans = child(spans, i);
+            let Some(value_type) = self.check(expression, value_spans) else {
+                is_valid = false;
+                continue;
+            };
+            let (is_printable, expected) = match format {
+                Some(_) => (value_type.is_numeric(), "number"),
+                None => (
+                    value_type.is_numeric()
+                        || matches!(value_type, Type::Bool | Type::Str | Type::DateTime),
+                    "number, string or bool",
+                ),
+            };
+            if !is_printable && value_type != Type::Null {
+                is_valid = false;
+                self.error(
+                    value_spans,
+                    TypeErrorKind::ExpectedTypeXFoundY {
+                        expected,
+                        found: value_type,
+                    },
+                );
+            }
+        }
+        is_valid.then_some(Type::Str)
+    }
+
+    /// `.member` on a value that is not a map calls the function of that name
+    /// without further arguments, e.g. `[name].Length`.
+    fn check_property(&mut self, member: &str, receiver: Type, spans: &SpanTree) -> Option<Type> {
+        let call_spans = SpanTree {
+            span: spans.span,
+            children: vec![
+                SpanTree {
+                    span: spans.span,
+                    children: vec![],
+                },
+                child(spans, 0).clone(),
+            ],
+        };
+        let error_count = self.errors.len();
+        let result = self.check_named_fn_call(member, &[Some(receiver.clone())], &call_spans);
+        match self.errors.get(error_count) {
+            // Not a function either: most likely a member of the wrong value.
+            Some(TypeError {
+                kind: TypeErrorKind::UnknownFunction(_),
+                ..
+            }) => {
+                self.errors.truncate(error_count);
+                self.error(
+                    child(spans, 0),
+                    TypeErrorKind::ExpectedTypeXFoundY {
+                        expected: "map",
+                        found: receiver,
+                    },
+                )
+            }
+            _ => result,
+        }
+    }
+
+    fn check_index_expression(
+        &mut self,
+        expression: &Expression,
+        index: &Expression,
+        spans: &SpanTree,
+    ) -> Option<Type> {
+        let expression_type = self.check(expression, child(spans, 0));
+        let index_type = self.check(index, child(spans, 1));
+        if let Some(index_type) = &index_type {
+            if !index_type.is_numeric() && *index_type != Type::Null {
+                self.error(
+                    child(spans, 1),
+                    TypeErrorKind::ExpectedTypeXFoundY {
+                        expected: "int",
+                        found: index_type.clone(),
+                    },
+                );
+            }
+        }
+        match expression_type? {
+            Type::List(element_type) => Some(*element_type),
+            Type::Null => Some(Type::Null),
+            found => self.error(
+                child(spans, 0),
+                TypeErrorKind::ExpectedTypeXFoundY {
+                    expected: "list",
+                    found,
+                },
+            ),
+        }
+    }
+
+    fn check_prefix_expression(
+        &mut self,
+        operator: &Token,
+        expression: &Expression,
+        spans: &SpanTree,
+    ) -> Option<Type> {
+        let literal_result = match (operator, expression) {
+            // The negation of the smallest int is read as that of a big integer.
+            (Token::Minus, Expression::BigInt(_)) => {
+                self.literal_result(Expression::PrefixExpression {
+                    operator: operator.clone(),
+                    expression: Box::new(expression.clone()),
+                })
+            }
+            _ => None,
+        };
+        let expression_type = self.check(expression, child(spans, 0))?;
+        match operator {
+            _ if literal_result.is_some() => literal_result,
+            _ if expression_type == Type::Null => Some(Type::Null),
+            Token::Minus if expression_type.is_numeric() => Some(expression_type.clone()),
+            Token::Minus => self.error(
+                spans,
+                TypeErrorKind::ExpectedTypeXFoundY {
+                    expected: "number",
+                    found: expression_type,
+                },
+            ),
+            _ if expression_type == Type::Bool => Some(Type::Bool),
+            _ => self.error(
+                spans,
+                TypeErrorKind::ExpectedTypeXFoundY {
+                    expected: "bool",
+                    found: expression_type,
+                },
+            ),
+        }
+    }
+
+    fn check_infix_expression(
+        &mut self,
+        operator: &Token,
+        left: &Expression,
+        right: &Expression,
+        spans: &SpanTree,
+    ) -> Option<Type> {
+        // Literals are never negative, a '-' in front is a prefix operator.
+        let is_literal_exponent = matches!(right, Expression::Int(_) | Expression::BigInt(_));
+        let literal_result = match is_number_literal(left) && is_number_literal(right) {
+            true => self.literal_result(Expression::InfixExpression {
+                operator: operator.clone(),
+                left: Box::new(left.clone()),
+                right: Box::new(right.clone()),
+            }),
+            false => None,
+        };
+        let left = self.check(left, child(spans, 0));
+        let right = self.check(right, child(spans, 1));
+        let (left, right) = (left?, right?);
+        if literal_result.is_some() {
+            return literal_result;
+        }
+        let promote_to_big_int = self.schema.options.promote_to_big_int;
+        let is_null_or = |operand: &Type, is_valid: fn(&Type) -> bool| {
+            *operand == Type::Null || is_valid(operand)
+        };
+        let is_scalar =
+            |operand: &Type| operand.is_numeric() || matches!(operand, Type::Bool | Type::Str);
+        let result = match operator {
+            // Arithmetic and comparisons with a null operand evaluate to null.
+            Token::Plus
+            | Token::Minus
+            | Token::Asterisk
+            | Token::Slash
+            | Token::DoubleSlash
+            | Token::Percent
+            | Token::DoubleAsterisk
+            | Token::LessThan
+            | Token::LessThanEqualTo
+            | Token::GreaterThan
+            | Token::GreaterThanEqualTo
+            | Token::Equals
+            | Token::NotEquals
+            | Token::NotEqualsAngleBrackets
+                if (left == Type::Null || right == Type::Null)
+                    && is_null_or(&left, is_scalar)
+                    && is_null_or(&right, is_scalar) =>
+            {
+                Some(Type::Null)
+            }
+            Token::DoubleSlash | Token::Percent if left == Type::Int && right == Type::Int => {
+                Some(Type::Int)
+            }
+            // A power with a decimal operand is a decimal for an integer
+            // exponent, and only approximate, so a double, for a fractional
+            // one. Unless it is an integer type, the exponent could be either.
+            Token::DoubleAsterisk
+                if (left == Type::Decimal || right == Type::Decimal)
+                    && left.is_numeric()
+                    && right.is_numeric()
+                    && !matches!(right, Type::Int | Type::BigInt) =>
+            {
+                Some(Type::Double)
+            }
+            // Division is not exact for big integers.
+            Token::Slash if left.arithmetic_result(&right) == Type::BigInt => Some(Type::Double),
+            // Like the evaluator, a power of big integers is a big integer when
+            // the exponent is not negative, which is only known for literals.
+            Token::DoubleAsterisk if left.arithmetic_result(&right) == Type::BigInt => {
+                match is_literal_exponent {
+                    true => Some(Type::BigInt),
+                    false => Some(Type::Double),
+                }
+            }
+            // The evaluator gives an int when the exponent is not negative and
+            // the result fits, and a double otherwise. That depends on the
+            // values, so unless both are literals it is typed as the wider
+            // double.
+            Token::DoubleAsterisk if left == Type::Int && right == Type::Int => Some(Type::Double),
+            // Exact when promoting, a big integer only if the result does not fit.
+            Token::Plus | Token::Minus | Token::Asterisk
+                if promote_to_big_int && left == Type::Int && right == Type::Int =>
+            {
+                Some(Type::Int)
+            }
+            Token::Plus
+            | Token::Minus
+            | Token::Asterisk
+            | Token::Slash
+            | Token::DoubleSlash
+            | Token::Percent
+            | Token::DoubleAsterisk
+                if left.is_numeric() && right.is_numeric() =>
+            {
+                Some(left.arithmetic_result(&right))
+            }
+            Token::LessThan
+            | Token::LessThanEqualTo
+            | Token::GreaterThan
+            | Token::GreaterThanEqualTo
+                if left.is_numeric() && right.is_numeric() =>
+            {
+                Some(Type::Bool)
+            }
+            Token::Equals | Token::NotEquals | Token::NotEqualsAngleBrackets
+                if (left.is_numeric() && right.is_numeric())
+                    || (left == Type::Bool && right == Type::Bool)
+                    || (left == Type::Str && right == Type::Str) =>
+            {
+                Some(Type::Bool)
+            }
+            Token::And | Token::DoubleAmpersand | Token::Or | Token::DoublePipe
+                if is_null_or(&left, |operand| *operand == Type::Bool)
+                    && is_null_or(&right, |operand| *operand == Type::Bool) =>
+            {
+                Some(Type::Bool)
+            }
+            Token::DoubleQuestionMark => left.clone().unify(right.clone()),
+            Token::In | Token::NotIn => match &right {
+                Type::Null => Some(Type::Null),
+                Type::List(element_type) => left
+                    .clone()
+                    .unify(*element_type.clone())
+                    .map(|_| Type::Bool),
+                _ => None,
+            },
+            _ => None,
+        };
+        result.or_else(|| {
+            self.error(
+                spans,
+                TypeErrorKind::UnExpectedOperatorOperandFound {
+                    operator: operator.clone(),
+                    left,
+                    right,
+                },
+            )
+        })
+    }
+
+    fn check_fn_call(
+        &mut self,
+        function: &Expression,
+        arguments: &[Box<Expression>],
+        spans: &SpanTree,
+    ) -> Option<Type> {
+        match function {
+            Expression::Ident(function_name)
+                if matches!(function_name.as_str(), "map" | "filter" | "reduce") =>
+            {
+                return self.check_lambda_fn_call(function_name, arguments, spans);
+            }
+            // Checked as the call of the function with the receiver as first
+            // argument, like the evaluator does.
+            Expression::MemberExpression {
+                expression: receiver,
+                member,
+                ..
+            } => {
+                let arguments: Vec<Box<Expression>> = std::iter::once(receiver.clone())
+                    .chain(arguments.iter().cloned())
+                    .collect();
+                let function_spans = child(spans, 0);
+                let spans = SpanTree {
+                    span: spans.span,
+                    children: [function_spans, child(function_spans, 0)]
+                        .into_iter()
+                        .chain(spans.children.iter().skip(1))
+                        .cloned()
+                        .collect(),
+                };
+                return self.check_fn_call(&Expression::Ident(member.clone()), &arguments, &spans);
+            }
+            _ => (),
+        }
+        let argument_types: Vec<Option<Type>> = arguments
+            .iter()
+            .enumerate()
+            .map(|(i, argument)| self.check(argument, child(spans, i + 1)))
+            .collect();
+
+        let Expression::Ident(function_name) = function else {
+            return self.error(
+                child(spans, 0),
+                TypeErrorKind::UnknownFunction(format!("{:?}", function)),
+            );
+        };
+        self.check_named_fn_call(function_name, &argument_types, spans)
+    }
+
+    /// Checks a call of `function_name` whose arguments have the given types,
+    /// `None` for those that failed to type check. The children of `spans` are
+    /// the function name followed by the arguments.
+    fn check_named_fn_call(
+        &mut self,
+        function_name: &str,
+        argument_types: &[Option<Type>],
+        spans: &SpanTree,
+    ) -> Option<Type> {
+        match function_name {
+            "if" => return self.check_if_fn_call(argument_types, spans),
+            "isnull" => {
+                return match argument_types.len() {
+                    1 => Some(Type::Bool),
+                    found => self.error(
+                        spans,
+                        TypeErrorKind::MissMatchFunctionArguements { expected: 1, found },
+                    ),
+                }
+            }
+            "ifnull" => {
+                let [value, fallback] = argument_types else {
+                    return self.error(
+                        spans,
+                        TypeErrorKind::MissMatchFunctionArguements {
+                            expected: 2,
+                            found: argument_types.len(),
+                        },
+                    );
+                };
+                return self.unify_branches(value.clone()?, fallback.clone()?, spans);
+            }
+            "Sum" | "Avg" | "Count" | "Min" | "Max" | "Distinct" | "Sort" | "Any" | "All" => {
+                return self.check_aggregate_fn_call(function_name, argument_types, spans)
+            }
+            "ToUpper" | "ToLower" | "Trim" | "Length" => {
+                let [argument_type] = argument_types else {
+                    return self.error(
+                        spans,
+                        TypeErrorKind::MissMatchFunctionArguements {
+                            expected: 1,
+                            found: argument_types.len(),
+                        },
+                    );
+                };
+                let returns = match function_name {
+                    "Length" => Type::Int,
+                    _ => Type::Str,
+                };
+                return match argument_type.clone()? {
+                    Type::Null => Some(Type::Null),
+                    Type::Str => Some(returns),
+                    Type::List(_) if function_name == "Length" => Some(returns),
+                    found => self.error(
+                        child(spans, 1),
+                        TypeErrorKind::ExpectedTypeXFoundY {
+                            expected: "string",
+                            found,
+                        },
+                    ),
+                };
+            }
+            "Round" | "Floor" | "Ceiling" | "Truncate" => {
+                let (value_type, digits_type) = match (function_name, argument_types) {
+                    (_, [value_type]) => (value_type, &Some(Type::Int)),
+                    ("Round", [value_type, digits_type]) => (value_type, digits_type),
+                    _ => {
+                        return self.error(
+                            spans,
+                            TypeErrorKind::MissMatchFunctionArguements {
+                                expected: if function_name == "Round" { 2 } else { 1 },
+                                found: argument_types.len(),
+                            },
+                        )
+                    }
+                };
+                let digits_type = match digits_type.clone()? {
+                    found @ (Type::Null | Type::Int) => found,
+                    found => {
+                        return self.error(
+                            child(spans, 2),
+                            TypeErrorKind::ExpectedTypeXFoundY {
+                                expected: "int",
+                                found,
+                            },
+                        )
+                    }
+                };
+                return match value_type.clone()? {
+                    _ if digits_type == Type::Null => Some(Type::Null),
+                    found if found == Type::Null || found.is_numeric() => Some(found),
+                    found => self.error(
+                        child(spans, 1),
+                        TypeErrorKind::ExpectedTypeXFoundY {
+                            expected: "number",
+                            found,
+                        },
+                    ),
+                };
+            }
+            "coalesce" => {
+                let mut result = Type::Null;
+                for argument_type in argument_types {
+                    result = self.unify_branches(result, argument_type.clone()?, spans)?;
+                }
+                return Some(result);
+            }
+            _ => (),
+        }
+        let Some(signature) = self.schema.functions.get(function_name) else {
+            return self.error(
+                child(spans, 0),
+                TypeErrorKind::UnknownFunction(function_name.to_string()),
+            );
+        };
+        let signature = signature.clone();
+        if signature.parameters.len() != argument_types.len() {
+            return self.error(
+                spans,
+                TypeErrorKind::MissMatchFunctionArguements {
+                    expected: signature.parameters.len(),
+                    found: argument_types.len(),
+                },
+            );
+        }
+
+        let mut arguments_match = true;
+        for (i, (found, expected)) in argument_types.iter().zip(&signature.parameters).enumerate() {
+            match found {
+                Some(found) if !found.is_assignable_to(expected) => {
+                    arguments_match = false;
+                    self.error(
+                        child(spans, i + 1),
+                        TypeErrorKind::ExpectedTypeXFoundY {
+                            expected: type_name(expected),
+                            found: found.clone(),
+                        },
+                    );
+                }
+                Some(_) => (),
+                None => arguments_match = false,
+            }
+        }
+        arguments_match.then_some(signature.returns)
+    }
+
+    /// `map`, `filter` and `reduce`, whose lambda is checked with its
+    /// parameters bound to the type of the list elements (and of the
+    /// accumulator for `reduce`).
+    fn check_lambda_fn_call(
+        &mut self,
+        function_name: &str,
+        arguments: &[Box<Expression>],
+        spans: &SpanTree,
+    ) -> Option<Type> {
+        let is_reduce = function_name == "reduce";
+        let expected = if is_reduce { 3 } else { 2 };
+        if arguments.len() != expected {
+            return self.error(
+                spans,
+                TypeErrorKind::MissMatchFunctionArguements {
+                    expected,
+                    found: arguments.len(),
+                },
+            );
+        }
+        let element_type = match self.check(&arguments[0], child(spans, 1))? {
+            Type::List(element_type) => *element_type,
+            Type::Null => Type::Null,
+            found => {
+                return self.error(
+                    child(spans, 1),
+                    TypeErrorKind::ExpectedTypeXFoundY {
+                        expected: "list",
+                        found,
+                    },
+                )
+            }
+        };
+        let initial_type = match is_reduce {
+            true => Some(self.check(&arguments[1], child(spans, 2))?),
+            false => None,
+        };
+
+        let lambda_spans = child(spans, arguments.len());
+        let arity = if is_reduce { 2 } else { 1 };
+        let Expression::Lambda { parameters, body } = arguments[arguments.len() - 1].as_ref()
+        else {
+            return self.error(lambda_spans, TypeErrorKind::ExpectedLambda { arity });
+        };
+        if parameters.len() != arity {
+            return self.error(lambda_spans, TypeErrorKind::ExpectedLambda { arity });
+        }
+        let parameter_types = initial_type.iter().cloned().chain([element_type.clone()]);
+        self.scopes.push(
+            parameters
+                .iter()
+                .map(|parameter| parameter.to_string())
+                .zip(parameter_types)
+                .collect(),
+        );
+        let body_spans = child(lambda_spans, 0);
+        let body_type = self.check(body, body_spans);
+        self.scopes.pop();
+        let body_type = body_type?;
+
+        match (function_name, initial_type) {
+            ("map", _) => Some(Type::List(Box::new(body_type))),
+            ("filter", _) if matches!(body_type, Type::Bool | Type::Null) => {
+                Some(Type::List(Box::new(element_type)))
+            }
+            ("filter", _) => self.error(
+                body_spans,
+                TypeErrorKind::ExpectedTypeXFoundY {
+                    expected: "bool",
+                    found: body_type,
+                },
+            ),
+            (_, initial_type) => {
+                let initial_type = initial_type.unwrap_or(Type::Null);
+                match initial_type.clone().unify(body_type.clone()) {
+                    Some(unified) => Some(unified),
+                    None => self.error(
+                        body_spans,
+                        TypeErrorKind::ExpectedTypeXFoundY {
+                            expected: type_name(&initial_type),
+                            found: body_type,
+                        },
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Like in the evaluator, an aggregate takes either one list or the values to
+    /// aggregate as separate arguments.
+    fn check_aggregate_fn_call(
+        &mut self,
+        function_name: &str,
+        argument_types: &[Option<Type>],
+        spans: &SpanTree,
+    ) -> Option<Type> {
+        let element_type = match argument_types {
+            [Some(Type::List(element_type))] => *element_type.clone(),
+            argument_types => {
+                let mut element_type = Type::Null;
+                for (i, argument_type) in argument_types.iter().enumerate() {
+                    let argument_type = argument_type.clone()?;
+                    element_type = match element_type.clone().unify(argument_type.clone()) {
+                        Some(unified) => unified,
+                        None => {
+                            return self.error(
+                                child(spans, i + 1),
+                                TypeErrorKind::ExpectedTypeXFoundY {
+                                    expected: type_name(&element_type),
+                                    found: argument_type,
+                                },
+                            )
+                        }
+                    }
+                }
+                element_type
+            }
+        };
+        let is_numeric = element_type.is_numeric() || element_type == Type::Null;
+        let (is_valid, expected, returns) = match function_name {
+            "Count" => (true, "", Type::Int),
+            // Like `+`, the sum of ints stays exact when promoting.
+            "Sum"
+                if self.schema.options.promote_to_big_int
+                    && matches!(element_type, Type::Int | Type::Null) =>
+            {
+                (true, "", Type::Int)
+            }
+            "Sum" | "Avg" => (
+                is_numeric,
+                "number",
+                Type::Double.arithmetic_result(&element_type),
+            ),
+            "Min" | "Max" => (is_numeric, "number", element_type.clone()),
+            "Distinct" => (true, "", Type::List(Box::new(element_type.clone()))),
+            "Sort" => (
+                is_numeric || element_type == Type::Str,
+                "number or string",
+                Type::List(Box::new(element_type.clone())),
+            ),
+            _ => (
+                matches!(element_type, Type::Bool | Type::Null),
+                "bool",
+                Type::Bool,
+            ),
+        };
+        match is_valid {
+            true => Some(returns),
+            false => self.error(
+                spans,
+                TypeErrorKind::ExpectedTypeXFoundY {
+                    expected,
+                    found: element_type,
+                },
+            ),
+        }
+    }
+
+    fn check_if_fn_call(
+        &mut self,
+        argument_types: &[Option<Type>],
+        spans: &SpanTree,
+    ) -> Option<Type> {
+        let [condition, when_true, when_false] = argument_types else {
+            return self.error(
+                spans,
+                TypeErrorKind::MissMatchFunctionArguements {
+                    expected: 3,
+                    found: argument_types.len(),
+                },
+            );
+        };
+        if let Some(condition) = condition {
+            if !matches!(condition, Type::Bool | Type::Null) {
+                self.error(
+                    child(spans, 1),
+                    TypeErrorKind::ExpectedTypeXFoundY {
+                        expected: "bool",
+                        found: condition.clone(),
+                    },
+                );
+            }
+        }
+        self.unify_branches(when_true.clone()?, when_false.clone()?, spans)
+    }
+
+    fn unify_branches(
+        &mut self,
+        when_true: Type,
+        when_false: Type,
+        spans: &SpanTree,
+    ) -> Option<Type> {
+        match when_true.clone().unify(when_false.clone()) {
+            Some(unified) => Some(unified),
+            None => self.error(
+                spans,
+                TypeErrorKind::MissMatchBranchTypes {
+                    when_true,
+                    when_false,
+                },
+            ),
+        }
+    }
+}
+
+fn is_number_literal(expression: &Expression) -> bool {
+    matches!(
+        expression,
+        Expression::Int(_) | Expression::Double(_) | Expression::Decimal(_) | Expression::BigInt(_)
+    )
+}
+
+fn child(spans: &SpanTree, index: usize) -> &SpanTree {
+    spans.children.get(index).unwrap_or(spans)
+}
+
+fn type_name(value: &Type) -> &'static str {
+    match value {
+        Type::Int => "int",
+        Type::Double => "double",
+        Type::Decimal => "decimal",
+        Type::BigInt => "bigint",
+        Type::Bool => "bool",
+        Type::Str => "string",
+        Type::DateTime => "datetime",
+        Type::Null => "null",
+        Type::List(_) => "list",
+        Type::Map(_) => "map",
+    }
+}
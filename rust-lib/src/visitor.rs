@@ -0,0 +1,450 @@
+//! Traversal of [`Expression`] trees.
+//!
+//! [`Visitor`] walks a tree by reference and [`Fold`] rebuilds it by value.
+//! Every method defaults to visiting the children of its node through the free
+//! function of the same name, so an implementation only overrides the nodes it
+//! cares about and calls that function to keep walking below them.
+//!
+//! The name of a called function is not treated as an identifier: it goes
+//! through `visit_function_name`/`fold_function_name` rather than
+//! `visit_ident`/`fold_ident`. So does the name of a method call such as
+//! `[name].ToUpper()`, with the receiver counted as its first argument.
+
+use std::rc::Rc;
+
+use crate::{
+    expression::{Expression, TemplatePart},
+    token::Token,
+};
+
+pub trait Visitor {
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression)
+    }
+
+    fn visit_prefix_expression(&mut self, operator: &Token, expression: &Expression) {
+        walk_prefix_expression(self, operator, expression)
+    }
+
+    fn visit_infix_expression(&mut self, operator: &Token, left: &Expression, right: &Expression) {
+        walk_infix_expression(self, operator, left, right)
+    }
+
+    fn visit_call_expression(&mut self, function: &Expression, arguments: &[Box<Expression>]) {
+        walk_call_expression(self, function, arguments)
+    }
+
+    fn visit_list(&mut self, elements: &[Box<Expression>]) {
+        walk_list(self, elements)
+    }
+
+    fn visit_index_expression(&mut self, expression: &Expression, index: &Expression) {
+        walk_index_expression(self, expression, index)
+    }
+
+    fn visit_lambda(&mut self, parameters: &[Rc<String>], body: &Expression) {
+        walk_lambda(self, parameters, body)
+    }
+
+    fn visit_member_expression(
+        &mut self,
+        expression: &Expression,
+        member: &Rc<String>,
+        null_safe: bool,
+    ) {
+        walk_member_expression(self, expression, member, null_safe)
+    }
+
+    fn visit_let(&mut self, name: &Rc<String>, value: &Expression, body: &Expression) {
+        walk_let(self, name, value, body)
+    }
+
+    fn visit_template(&mut self, parts: &[TemplatePart]) {
+        walk_template(self, parts)
+    }
+
+    /// A `[name]` parameter.
+    fn visit_ident(&mut self, _ident: &Rc<String>) {}
+
+    /// A name written without brackets.
+    fn visit_bare_ident(&mut self, _ident: &Rc<String>) {}
+
+    fn visit_function_name(&mut self, _name: &Rc<String>, _arity: usize) {}
+
+    /// `Nil`, `null`, numbers, bools and strings.
+    fn visit_literal(&mut self, _literal: &Expression) {}
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::Ident(ident) => visitor.visit_ident(ident),
+        Expression::BareIdent(ident) => visitor.visit_bare_ident(ident),
+        Expression::PrefixExpression {
+            operator,
+            expression,
+        } => visitor.visit_prefix_expression(operator, expression),
+        Expression::InfixExpression {
+            operator,
+            left,
+            right,
+        } => visitor.visit_infix_expression(operator, left, right),
+        Expression::CallExpression {
+            function,
+            arguments,
+        } => visitor.visit_call_expression(function, arguments),
+        Expression::List(elements) => visitor.visit_list(elements),
+        Expression::IndexExpression { expression, index } => {
+            visitor.visit_index_expression(expression, index)
+        }
+        Expression::Lambda { parameters, body } => visitor.visit_lambda(parameters, body),
+        Expression::MemberExpression {
+            expression,
+            member,
+            null_safe,
+        } => visitor.visit_member_expression(expression, member, *null_safe),
+        Expression::Let { name, value, body } => visitor.visit_let(name, value, body),
+        Expression::Template(parts) => visitor.visit_template(parts),
+        Expression::Nil
+        | Expression::Int(_)
+        | Expression::Double(_)
+        | Expression::Decimal(_)
+        | Expression::BigInt(_)
+        | Expression::Bool(_)
+        | Expression::Str(_)
+        | Expression::Null => visitor.visit_literal(expression),
+    }
+}
+
+pub fn walk_prefix_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    _operator: &Token,
+    expression: &Expression,
+) {
+    visitor.visit_expression(expression)
+}
+
+pub fn walk_infix_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    _operator: &Token,
+    left: &Expression,
+    right: &Expression,
+) {
+    visitor.visit_expression(left);
+    visitor.visit_expression(right);
+}
+
+pub fn walk_call_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    function: &Expression,
+    arguments: &[Box<Expression>],
+) {
+    match function {
+        Expression::Ident(name) => visitor.visit_function_name(name, arguments.len()),
+        Expression::MemberExpression {
+            expression: receiver,
+            member,
+            ..
+        } => {
+            visitor.visit_expression(receiver);
+            visitor.visit_function_name(member, arguments.len() + 1);
+        }
+        function => visitor.visit_expression(function),
+    }
+    for argument in arguments {
+        visitor.visit_expression(argument);
+    }
+}
+
+pub fn walk_list<V: Visitor + ?Sized>(visitor: &mut V, elements: &[Box<Expression>]) {
+    for element in elements {
+        visitor.visit_expression(element);
+    }
+}
+
+pub fn walk_index_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &Expression,
+    index: &Expression,
+) {
+    visitor.visit_expression(expression);
+    visitor.visit_expression(index);
+}
+
+pub fn walk_lambda<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    _parameters: &[Rc<String>],
+    body: &Expression,
+) {
+    visitor.visit_expression(body)
+}
+
+pub fn walk_member_expression<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    expression: &Expression,
+    _member: &Rc<String>,
+    _null_safe: bool,
+) {
+    visitor.visit_expression(expression)
+}
+
+pub fn walk_let<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    _name: &Rc<String>,
+    value: &Expression,
+    body: &Expression,
+) {
+    visitor.visit_expression(value);
+    visitor.visit_expression(body);
+}
+
+pub fn walk_template<V: Visitor + ?Sized>(visitor: &mut V, parts: &[TemplatePart]) {
+    for part in parts {
+        if let TemplatePart::Value { expression, .. } = part {
+            visitor.visit_expression(expression);
+        }
+    }
+}
+
+pub trait Fold {
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        fold_expression(self, expression)
+    }
+
+    fn fold_prefix_expression(&mut self, operator: Token, expression: Expression) -> Expression {
+        fold_prefix_expression(self, operator, expression)
+    }
+
+    fn fold_infix_expression(
+        &mut self,
+        operator: Token,
+        left: Expression,
+        right: Expression,
+    ) -> Expression {
+        fold_infix_expression(self, operator, left, right)
+    }
+
+    #[allow(clippy::vec_box)]
+    fn fold_call_expression(
+        &mut self,
+        function: Expression,
+        arguments: Vec<Box<Expression>>,
+    ) -> Expression {
+        fold_call_expression(self, function, arguments)
+    }
+
+    #[allow(clippy::vec_box)]
+    fn fold_list(&mut self, elements: Vec<Box<Expression>>) -> Expression {
+        fold_list(self, elements)
+    }
+
+    fn fold_index_expression(&mut self, expression: Expression, index: Expression) -> Expression {
+        fold_index_expression(self, expression, index)
+    }
+
+    fn fold_lambda(&mut self, parameters: Vec<Rc<String>>, body: Expression) -> Expression {
+        fold_lambda(self, parameters, body)
+    }
+
+    fn fold_member_expression(
+        &mut self,
+        expression: Expression,
+        member: Rc<String>,
+        null_safe: bool,
+    ) -> Expression {
+        fold_member_expression(self, expression, member, null_safe)
+    }
+
+    fn fold_let(&mut self, name: Rc<String>, value: Expression, body: Expression) -> Expression {
+        fold_let(self, name, value, body)
+    }
+
+    fn fold_template(&mut self, parts: Vec<TemplatePart>) -> Expression {
+        fold_template(self, parts)
+    }
+
+    /// A `[name]` parameter.
+    fn fold_ident(&mut self, ident: Rc<String>) -> Expression {
+        Expression::Ident(ident)
+    }
+
+    /// A name written without brackets.
+    fn fold_bare_ident(&mut self, ident: Rc<String>) -> Expression {
+        Expression::BareIdent(ident)
+    }
+
+    fn fold_function_name(&mut self, name: Rc<String>, _arity: usize) -> Rc<String> {
+        name
+    }
+
+    /// `Nil`, `null`, numbers, bools and strings.
+    fn fold_literal(&mut self, literal: Expression) -> Expression {
+        literal
+    }
+}
+
+pub fn fold_expression<F: Fold + ?Sized>(folder: &mut F, expression: Expression) -> Expression {
+    match expression {
+        Expression::Ident(ident) => folder.fold_ident(ident),
+        Expression::BareIdent(ident) => folder.fold_bare_ident(ident),
+        Expression::PrefixExpression {
+            operator,
+            expression,
+        } => folder.fold_prefix_expression(operator, *expression),
+        Expression::InfixExpression {
+            operator,
+            left,
+            right,
+        } => folder.fold_infix_expression(operator, *left, *right),
+        Expression::CallExpression {
+            function,
+            arguments,
+        } => folder.fold_call_expression(*function, arguments),
+        Expression::List(elements) => folder.fold_list(elements),
+        Expression::IndexExpression { expression, index } => {
+            folder.fold_index_expression(*expression, *index)
+        }
+        Expression::Lambda { parameters, body } => folder.fold_lambda(parameters, *body),
+        Expression::MemberExpression {
+            expression,
+            member,
+            null_safe,
+        } => folder.fold_member_expression(*expression, member, null_safe),
+        Expression::Let { name, value, body } => folder.fold_let(name, *value, *body),
+        Expression::Template(parts) => folder.fold_template(parts),
+        Expression::Nil
+        | Expression::Int(_)
+        | Expression::Double(_)
+        | Expression::Decimal(_)
+        | Expression::BigInt(_)
+        | Expression::Bool(_)
+        | Expression::Str(_)
+        | Expression::Null => folder.fold_literal(expression),
+    }
+}
+
+pub fn fold_prefix_expression<F: Fold + ?Sized>(
+    folder: &mut F,
+    operator: Token,
+    expression: Expression,
+) -> Expression {
+    Expression::PrefixExpression {
+        operator,
+        expression: Box::new(folder.fold_expression(expression)),
+    }
+}
+
+pub fn fold_infix_expression<F: Fold + ?Sized>(
+    folder: &mut F,
+    operator: Token,
+    left: Expression,
+    right: Expression,
+) -> Expression {
+    Expression::InfixExpression {
+        operator,
+        left: Box::new(folder.fold_expression(left)),
+        right: Box::new(folder.fold_expression(right)),
+    }
+}
+
+#[allow(clippy::vec_box)]
+pub fn fold_call_expression<F: Fold + ?Sized>(
+    folder: &mut F,
+    function: Expression,
+    arguments: Vec<Box<Expression>>,
+) -> Expression {
+    let function = match function {
+        Expression::Ident(name) => {
+            Expression::Ident(folder.fold_function_name(name, arguments.len()))
+        }
+        Expression::MemberExpression {
+            expression: receiver,
+            member,
+            null_safe,
+        } => Expression::MemberExpression {
+            expression: Box::new(folder.fold_expression(*receiver)),
+            member: folder.fold_function_name(member, arguments.len() + 1),
+            null_safe,
+        },
+        function => folder.fold_expression(function),
+    };
+    Expression::CallExpression {
+        function: Box::new(function),
+        arguments: arguments
+            .into_iter()
+            .map(|argument| Box::new(folder.fold_expression(*argument)))
+            .collect(),
+    }
+}
+
+#[allow(clippy::vec_box)]
+pub fn fold_list<F: Fold + ?Sized>(folder: &mut F, elements: Vec<Box<Expression>>) -> Expression {
+    Expression::List(
+        elements
+            .into_iter()
+            .map(|element| Box::new(folder.fold_expression(*element)))
+            .collect(),
+    )
+}
+
+pub fn fold_index_expression<F: Fold + ?Sized>(
+    folder: &mut F,
+    expression: Expression,
+    index: Expression,
+) -> Expression {
+    Expression::IndexExpression {
+        expression: Box::new(folder.fold_expression(expression)),
+        index: Box::new(folder.fold_expression(index)),
+    }
+}
+
+pub fn fold_lambda<F: Fold + ?Sized>(
+    folder: &mut F,
+    parameters: Vec<Rc<String>>,
+    body: Expression,
+) -> Expression {
+    Expression::Lambda {
+        parameters,
+        body: Box::new(folder.fold_expression(body)),
+    }
+}
+
+pub fn fold_member_expression<F: Fold + ?Sized>(
+    folder: &mut F,
+    expression: Expression,
+    member: Rc<String>,
+    null_safe: bool,
+) -> Expression {
+    Expression::MemberExpression {
+        expression: Box::new(folder.fold_expression(expression)),
+        member,
+        null_safe,
+    }
+}
+
+pub fn fold_let<F: Fold + ?Sized>(
+    folder: &mut F,
+    name: Rc<String>,
+    value: Expression,
+    body: Expression,
+) -> Expression {
+    Expression::Let {
+        name,
+        value: Box::new(folder.fold_expression(value)),
+        body: Box::new(folder.fold_expression(body)),
+    }
+}
+
+pub fn fold_template<F: Fold + ?Sized>(folder: &mut F, parts: Vec<TemplatePart>) -> Expression {
+    Expression::Template(
+        parts
+            .into_iter()
+            .map(|part| match part {
+                TemplatePart::Value { expression, format } => TemplatePart::Value {
+                    expression: Box::new(folder.fold_expression(*expression)),
+                    format,
+                },
+                text => text,
+            })
+            .collect(),
+    )
+}